@@ -1,6 +1,8 @@
+use crate::generators::constraints;
 use crate::parse::{Field, FromStruct};
 use proc_macro2::TokenStream;
 use quote::{ToTokens, quote};
+use syn::ext::IdentExt;
 
 pub(crate) fn create(from_struct: &FromStruct) -> Vec<TokenStream> {
     from_struct
@@ -17,27 +19,57 @@ fn setter_impl_for(field: &Field, from_struct: &FromStruct) -> TokenStream {
         .unwrap_or(field.ident().to_token_stream());
     let field_type = field.ty();
 
-    let other_placeholder_field_type_ident = from_struct.fields.iter().filter_map(|other_field| {
-        if other_field.ident() == field.ident() && !field.has_multi() {
+    let groups = from_struct.groups();
+    let is_excluded_by_field = |other_field: &Field| {
+        groups.iter().any(|group| {
+            group.rule.is_exclusive() && group.contains(field) && group.contains(other_field)
+        })
+    };
+
+    // The value other fields must have, and keep, for the setter to be available. Whether the
+    // field itself is already set is checked by the setter, so that it reports the field.
+    let fixed_placeholder = |other_field: &Field| {
+        if other_field.ident() == field.ident() {
             None
+        } else if is_excluded_by_field(other_field) {
+            Some(false)
         } else {
-            Some(other_field.const_field_placeholder())
+            staged_placeholder(field, other_field, from_struct)
         }
-    });
+    };
 
-    let input_placeholder_field_type_ident = from_struct.fields.iter().map(|other_field| {
-        if other_field.ident() == field.ident() && !field.has_multi() {
-            quote! {false}
+    let free_placeholder_field_type_ident = from_struct.fields.iter().filter_map(|other_field| {
+        if fixed_placeholder(other_field).is_some() {
+            None
         } else {
-            other_field.field_placeholder()
+            Some(other_field.field_placeholder())
         }
     });
 
+    let input_placeholder_field_type_ident =
+        from_struct
+            .fields
+            .iter()
+            .map(|other_field| match fixed_placeholder(other_field) {
+                Some(is_set) => {
+                    let marker = from_struct.field_marker(other_field, is_set);
+                    quote! {#marker}
+                }
+                None => other_field.field_placeholder(),
+            });
+
     let output_placeholder_field_type_ident = from_struct.fields.iter().map(|other_field| {
-        if other_field.ident() == field.ident() {
-            quote! {true}
+        let fixed_placeholder = if other_field.ident() == field.ident() {
+            Some(true)
         } else {
-            other_field.field_placeholder()
+            fixed_placeholder(other_field)
+        };
+        match fixed_placeholder {
+            Some(is_set) => {
+                let marker = from_struct.field_marker(other_field, is_set);
+                quote! {#marker}
+            }
+            None => other_field.field_placeholder(),
         }
     });
 
@@ -50,22 +82,95 @@ fn setter_impl_for(field: &Field, from_struct: &FromStruct) -> TokenStream {
         }
     });
 
-    let generics = from_struct.generics.all();
+    let generics = from_struct.generics.state_all();
 
-    let all_generics_names1 = from_struct.generics.all_names();
-    let all_generics_names2 = all_generics_names1.clone();
+    let all_generics_names = from_struct.generics.all_names();
 
-    let where_clause = from_struct.generics.where_clause();
+    let where_clause = from_struct.generics.state_where_clause();
+
+    // Setting a field whose type is a type changing parameter changes the parameter to the type of
+    // the value, bounded as the parameter is.
+    let (value_type, setter_generics, setter_predicates, output_generics_names) =
+        match from_struct.generics.type_changing_param(field) {
+            Some(param) => {
+                let new_param = from_struct.unique_param_ident(&format!("New{}", param.unraw()));
+                let bounds = from_struct.generics.bounds_of(param, &new_param);
+                let setter_predicates = (!bounds.is_empty())
+                    .then(|| quote! {#new_param: #(#bounds)+*})
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                let output_generics_names = all_generics_names
+                    .iter()
+                    .map(|name| {
+                        if *param == name.to_string() {
+                            quote! {#new_param}
+                        } else {
+                            name.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                (
+                    quote! {#new_param},
+                    Some(quote! {<#new_param>}),
+                    setter_predicates,
+                    output_generics_names,
+                )
+            }
+            None => (
+                field_type.to_token_stream(),
+                None,
+                vec![],
+                all_generics_names.clone(),
+            ),
+        };
 
     let builder_state_ident = from_struct.builder_state_ident();
+    let markers_field_ident = from_struct.markers_field_ident();
+    let input_state_type = quote! {
+        #builder_state_ident<#(#all_generics_names,)*#(#input_placeholder_field_type_ident,)*>
+    };
+    let setter_predicates = constraints::setter_predicate(field, from_struct, &input_state_type)
+        .into_iter()
+        .chain(setter_predicates)
+        .collect::<Vec<_>>();
+    let setter_where_clause = (!setter_predicates.is_empty()).then(|| {
+        quote! {where #(#setter_predicates,)*}
+    });
+
     quote! {
-        impl<#(#generics,)*#(#other_placeholder_field_type_ident,)*> #builder_state_ident<#(#all_generics_names1,)*#(#input_placeholder_field_type_ident,)*> #where_clause {
-        fn #setter_ident(self, value: #field_type) -> #builder_state_ident<#(#all_generics_names2,)*#(#output_placeholder_field_type_ident,)*> {
+        impl<#(#generics,)*#(#free_placeholder_field_type_ident,)*> #input_state_type #where_clause {
+        fn #setter_ident #setter_generics(self, value: #value_type) -> #builder_state_ident<#(#output_generics_names,)*#(#output_placeholder_field_type_ident,)*> #setter_where_clause {
             #builder_state_ident {
-                #field_ident: Some(value),
+                #field_ident: ::core::option::Option::Some(value),
                 #(#copy_other_fields,)*
+                #markers_field_ident: ::core::marker::PhantomData,
                 }
             }
         }
     }
 }
+
+/// In staged builders the required fields are set in declaration order: the required fields
+/// declared before `field` must be set, and when `field` is required the ones after it must not.
+fn staged_placeholder(
+    field: &Field,
+    other_field: &Field,
+    from_struct: &FromStruct,
+) -> Option<bool> {
+    if !from_struct.is_staged() || other_field.has_default() {
+        return None;
+    }
+    let position = |field: &Field| {
+        from_struct
+            .fields
+            .iter()
+            .position(|candidate| candidate.ident() == field.ident())
+    };
+    if position(other_field) < position(field) {
+        Some(true)
+    } else if !field.has_default() {
+        Some(false)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,16 @@
+use crate::parse::FromStruct;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Imports the `alloc` crate under a name of its own for the generated code collecting errors,
+/// so that it doesn't need `std`, nor the user crate to declare `extern crate alloc`.
+pub(crate) fn create(from_struct: &FromStruct) -> Option<TokenStream> {
+    if !from_struct.is_dynamic() && !from_struct.is_serde() {
+        return None;
+    }
+
+    let alloc = from_struct.alloc_crate_ident();
+    Some(quote! {
+        extern crate alloc as #alloc;
+    })
+}
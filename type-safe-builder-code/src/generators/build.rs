@@ -1,56 +1,538 @@
-use crate::parse::{DefaultToSet, FromStruct};
-use proc_macro2::TokenStream;
+use crate::generators::constraints;
+use crate::parse::{DefaultToSet, Field, FromStruct, replace_params, replace_self};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::quote;
+use syn::ext::IdentExt;
 
 pub(crate) fn create(from_struct: &FromStruct) -> TokenStream {
-    let all_not_default_set = from_struct.fields.iter().map(|field| {
-        if field.has_default() {
-            field.field_placeholder()
-        } else {
-            quote! {
-                true
+    let value_or_default_trait = value_or_default_trait(from_struct);
+    let build_impl = build_impl(from_struct);
+    let value_or_base_trait = value_or_base_trait(from_struct);
+    let build_over_impl = build_over(from_struct);
+
+    quote! {
+        #value_or_default_trait
+
+        #build_impl
+
+        #value_or_base_trait
+
+        #build_over_impl
+    }
+}
+
+/// The fields with a default value whose type is a type changing parameter, which still has its
+/// default type when the field isn't set, that is the type of the default value.
+fn defaulted_type_changing_fields(from_struct: &FromStruct) -> Vec<(&Field, &Ident)> {
+    from_struct
+        .fields
+        .iter()
+        .filter(|field| field.has_default())
+        .filter_map(|field| {
+            from_struct
+                .generics
+                .type_changing_param(field)
+                .map(|param| (field, param))
+        })
+        .collect()
+}
+
+/// A trait implemented by the markers of the fields whose type is a type changing parameter
+/// with a default type, giving the value of the field, of type `T`, out of the default value of
+/// type `D`. Only the unset markers have `D` be `T`, so that a single build method can build
+/// the struct whether these fields are set or not.
+fn value_or_default_trait(from_struct: &FromStruct) -> Option<TokenStream> {
+    let defaulted_type_changing_fields = defaulted_type_changing_fields(from_struct);
+    if defaulted_type_changing_fields.is_empty() {
+        return None;
+    }
+
+    let trait_ident = from_struct.value_or_default_trait_ident();
+    let vis = &from_struct.vis;
+
+    let all_impls = defaulted_type_changing_fields.iter().map(|(field, _)| {
+        let set_markers = set_markers(field, from_struct);
+        let unset_marker = from_struct.field_marker(field, false);
+        quote! {
+            #(
+                impl<T, D> #trait_ident<T, D> for #set_markers {
+                    fn value_or_default(
+                        value: ::core::option::Option<T>,
+                        _: impl ::core::ops::FnOnce() -> D,
+                    ) -> T {
+                        value.unwrap()
+                    }
+                }
+            )*
+
+            impl<T> #trait_ident<T, T> for #unset_marker {
+                fn value_or_default(
+                    value: ::core::option::Option<T>,
+                    default: impl ::core::ops::FnOnce() -> T,
+                ) -> T {
+                    value.unwrap_or_else(default)
+                }
+            }
+        }
+    });
+
+    Some(quote! {
+        /// Gives the value of a field whose type is a parameter with a default type.
+        #vis trait #trait_ident<T, D> {
+            /// The value of the field, or the default value if the field isn't set.
+            fn value_or_default(
+                value: ::core::option::Option<T>,
+                default: impl ::core::ops::FnOnce() -> D,
+            ) -> T;
+        }
+
+        #(#all_impls)*
+    })
+}
+
+/// The build method. Required fields are left free, so that building before they are set reports
+/// them.
+fn build_impl(from_struct: &FromStruct) -> TokenStream {
+    let all_placeholder_fields = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder())
+        .collect::<Vec<_>>();
+
+    let all_generics = from_struct.generics.all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let builder_state_ident = from_struct.builder_state_ident();
+    let builder_state_type = quote! {
+        #builder_state_ident<#(#all_generics_names,)*#(#all_placeholder_fields,)*>
+    };
+
+    let defaulted_type_changing_fields = defaulted_type_changing_fields(from_struct);
+    let trait_ident = from_struct.value_or_default_trait_ident();
+    let value_or_default_predicates =
+        defaulted_type_changing_fields.iter().map(|(field, param)| {
+            let field_placeholder = field.field_placeholder();
+            let default_type = from_struct
+                .generics
+                .replace_with_defaults(quote! {#param}, &[param]);
+            quote! {#field_placeholder: #trait_ident<#param, #default_type>}
+        });
+    let known_set_fields = defaulted_type_changing_fields
+        .iter()
+        .map(|(field, _)| *field)
+        .collect::<Vec<_>>();
+    let where_clause = from_struct.generics.where_clause_with(
+        predicates_knowing(from_struct, &known_set_fields)
+            .into_iter()
+            .chain(value_or_default_predicates)
+            .collect(),
+    );
+    let build_predicates = constraints::build_predicates(from_struct, &builder_state_type);
+    let build_where_clause = (!build_predicates.is_empty()).then(|| {
+        quote! {where #(#build_predicates,)*}
+    });
+
+    let built_type = from_struct.built_type();
+
+    let struct_body = resolve_and_construct(from_struct, &quote! {self}, true);
+    let into_built_type = into_built_type(from_struct);
+
+    quote! {
+        impl <#(#all_generics,)*#(#all_placeholder_fields,)*> #builder_state_type #where_clause{
+            fn build(self) -> #built_type #build_where_clause {
+                let built = {
+                    #struct_body
+                };
+                #into_built_type
+            }
+        }
+    }
+}
+
+fn uses_struct_default(from_struct: &FromStruct) -> bool {
+    !struct_default_fields(from_struct).is_empty()
+}
+
+/// The fields taking their default value from the `Default` implementation of the struct.
+fn struct_default_fields(from_struct: &FromStruct) -> Vec<&Field> {
+    from_struct
+        .fields
+        .iter()
+        .filter(|field| {
+            matches!(
+                field.default_to_set(),
+                Some(DefaultToSet::FromStructDefault)
+            )
+        })
+        .collect()
+}
+
+/// The predicates, in addition to the struct ones, needed to build the struct from the fields that have been set.
+pub(crate) fn predicates(from_struct: &FromStruct) -> Vec<TokenStream> {
+    predicates_knowing(from_struct, &[])
+}
+
+/// Like [`predicates`], knowing that `set_fields` are set, so their default value isn't needed.
+fn predicates_knowing(from_struct: &FromStruct, set_fields: &[&Field]) -> Vec<TokenStream> {
+    let struct_default = uses_struct_default(from_struct).then(|| {
+        let self_type = from_struct.self_type();
+        quote! {#self_type: ::core::default::Default}
+    });
+
+    let default_values_predicates = from_struct.bound().unwrap_or_else(|| {
+        from_struct
+            .fields
+            .iter()
+            .filter(|field| {
+                !set_fields
+                    .iter()
+                    .any(|set_field| set_field.ident() == field.ident())
+            })
+            .flat_map(|field| default_value_predicates(field, from_struct))
+            .collect()
+    });
+
+    struct_default
+        .into_iter()
+        .chain(default_values_predicates)
+        .collect()
+}
+
+/// The predicates needed by the default value of `field`: the `Default` implementation of its type
+/// can only be checked when building if the type is generic.
+fn default_value_predicates(field: &Field, from_struct: &FromStruct) -> Vec<TokenStream> {
+    if let Some(bound) = field.bound() {
+        return bound;
+    }
+    let field_type = field.ty();
+    match field.default_to_set() {
+        Some(DefaultToSet::AsDefault) if from_struct.generics.is_generic(field_type) => {
+            vec![quote! {#field_type: ::core::default::Default}]
+        }
+        _ => vec![],
+    }
+}
+
+/// Builds the struct out of `source`, whose fields are all `Option`s named after the struct fields.
+/// Fields that are not set must all have a default.
+pub(crate) fn body(from_struct: &FromStruct, source: &TokenStream) -> TokenStream {
+    let struct_body = struct_body(from_struct, source);
+    let into_built_type = into_built_type(from_struct);
+
+    quote! {
+        let built = {
+            #struct_body
+        };
+        #into_built_type
+    }
+}
+
+/// Like [`body`], but evaluates to the struct itself even when `build_into` is set.
+pub(crate) fn struct_body(from_struct: &FromStruct, source: &TokenStream) -> TokenStream {
+    resolve_and_construct(from_struct, source, false)
+}
+
+/// Like [`struct_body`], resolving the fields whose type is a type changing parameter with a
+/// default type through their marker if `through_markers`.
+fn resolve_and_construct(
+    from_struct: &FromStruct,
+    source: &TokenStream,
+    through_markers: bool,
+) -> TokenStream {
+    let struct_default = Ident::new("struct_default", Span::mixed_site());
+    let struct_default_fields = struct_default_fields(from_struct);
+    let create_struct_default = lazy_struct_default(from_struct, &struct_default_fields);
+
+    let self_expr = from_struct.self_expr();
+    let value_or_default_trait_ident = from_struct.value_or_default_trait_ident();
+
+    let resolve_all_fields = from_struct
+        .fields_in_default_order()
+        .into_iter()
+        .map(|field| {
+            let field_name = field.ident();
+            let field_local = from_struct.field_local(field);
+            let Some(default_to_set) = field.default_to_set() else {
+                return quote! {
+                    let #field_local = #source.#field_name.unwrap();
+                };
+            };
+            let is_default = matches!(default_to_set, DefaultToSet::AsDefault);
+            let default_value = match default_to_set {
+                DefaultToSet::AsDefault => quote! {::core::default::Default::default()},
+                DefaultToSet::AsValue(value) => replace_self(value, &self_expr),
+                DefaultToSet::WithFn(default_fn) => {
+                    let default_fn = replace_self(default_fn, &self_expr);
+                    quote! {#default_fn()}
+                }
+                DefaultToSet::FromStructDefault => {
+                    let index = struct_default_fields
+                        .iter()
+                        .position(|struct_default_field| struct_default_field.ident() == field_name)
+                        .map(syn::Index::from);
+                    quote! {
+                        ::core::option::Option::get_or_insert_with(&mut #struct_default, #create_struct_default)
+                            .#index
+                            .take()
+                            .unwrap()
+                    }
+                }
+            };
+            match from_struct.generics.type_changing_param(field) {
+                Some(param) if through_markers => {
+                    let field_placeholder = field.field_placeholder();
+                    let default_type = from_struct
+                        .generics
+                        .replace_with_defaults(quote! {#param}, &[param]);
+                    let default_value = from_struct
+                        .generics
+                        .replace_with_defaults(default_value, &[param]);
+                    quote! {
+                        let #field_local = <#field_placeholder as #value_or_default_trait_ident<#param, #default_type>>::value_or_default(
+                            #source.#field_name,
+                            || #default_value,
+                        );
+                    }
+                }
+                _ if is_default => quote! {
+                    let #field_local = #source.#field_name.unwrap_or_default();
+                },
+                _ => quote! {
+                    let #field_local = match #source.#field_name {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None => #default_value,
+                    };
+                },
             }
+        });
+
+    let construct_struct = construct(from_struct);
+
+    let declare_struct_default = (!struct_default_fields.is_empty()).then(|| {
+        quote! {
+            let mut #struct_default = ::core::option::Option::None;
         }
     });
 
-    let all_default_placeholder_fields_types = from_struct.fields.iter().filter_map(|field| {
-        if field.has_default() {
-            Some(field.const_field_placeholder())
-        } else {
-            None
+    quote! {
+        #declare_struct_default
+        #(#resolve_all_fields)*
+        #construct_struct
+        built
+    }
+}
+
+/// A closure creating the `Default` value of the struct, and splitting it into the `Option`s of the
+/// `struct_default_fields`, so that each of them could be taken out. It is only called by the
+/// first field that isn't set, so that the struct default isn't created when all fields are set.
+fn lazy_struct_default(from_struct: &FromStruct, struct_default_fields: &[&Field]) -> TokenStream {
+    let self_type = from_struct.self_type();
+    let from_struct_ident = from_struct.ident();
+    let field_idents = struct_default_fields.iter().map(|field| field.ident());
+    let values = (0..struct_default_fields.len())
+        .map(|index| Ident::new(&format!("value{index}"), Span::mixed_site()))
+        .collect::<Vec<_>>();
+
+    quote! {
+        || {
+            let #from_struct_ident { #(#field_idents: #values,)* .. } = <#self_type as ::core::default::Default>::default();
+            (#(::core::option::Option::Some(#values),)*)
+        }
+    }
+}
+
+/// A trait implemented by the markers of the fields whose type is a type changing parameter,
+/// giving the value of the field out of the value of type `T` it has been set to, or out of the
+/// base value of type `U`. Only the set markers have `U` be `T`, so that the base can choose the
+/// type of the fields that aren't set.
+fn value_or_base_trait(from_struct: &FromStruct) -> Option<TokenStream> {
+    let type_changing_fields = type_changing_fields(from_struct);
+    if type_changing_fields.is_empty() {
+        return None;
+    }
+
+    let trait_ident = from_struct.value_or_base_trait_ident();
+    let vis = &from_struct.vis;
+
+    let all_impls = type_changing_fields.iter().map(|(field, _)| {
+        let set_markers = set_markers(field, from_struct);
+        let unset_marker = from_struct.field_marker(field, false);
+        quote! {
+            #(
+                impl<T> #trait_ident<T, T> for #set_markers {
+                    fn value_or_base(value: ::core::option::Option<T>, _: T) -> T {
+                        value.unwrap()
+                    }
+                }
+            )*
+
+            impl<T, U> #trait_ident<T, U> for #unset_marker {
+                fn value_or_base(_: ::core::option::Option<T>, base: U) -> U {
+                    base
+                }
+            }
         }
     });
 
-    let copy_all_fields = from_struct.fields.iter().map(|field| {
+    Some(quote! {
+        /// Gives the value of a field whose type is a parameter when building over a base value.
+        #vis trait #trait_ident<T, U> {
+            /// The value of the field, or the base value if the field isn't set.
+            fn value_or_base(value: ::core::option::Option<T>, base: U) -> U;
+        }
+
+        #(#all_impls)*
+    })
+}
+
+/// The markers of the builder states where `field` is set, by a setter or to the value of a built
+/// struct.
+fn set_markers(field: &Field, from_struct: &FromStruct) -> Vec<Ident> {
+    let set_marker = from_struct.field_marker(field, true);
+    if from_struct.is_preset(field) {
+        vec![set_marker, from_struct.preset_marker(field)]
+    } else {
+        vec![set_marker]
+    }
+}
+
+/// The fields whose type is a type changing parameter.
+fn type_changing_fields(from_struct: &FromStruct) -> Vec<(&Field, &Ident)> {
+    from_struct
+        .fields
+        .iter()
+        .filter_map(|field| {
+            from_struct
+                .generics
+                .type_changing_param(field)
+                .map(|param| (field, param))
+        })
+        .collect()
+}
+
+/// The `build_over` method, taking the fields that aren't set from a base value. The type
+/// changing parameters of the fields that aren't set are the ones of the base value.
+fn build_over(from_struct: &FromStruct) -> TokenStream {
+    let base = Ident::new("base", Span::mixed_site());
+
+    let type_changing_fields = type_changing_fields(from_struct);
+    let base_params = type_changing_fields
+        .iter()
+        .map(|(_, param)| {
+            (
+                *param,
+                from_struct.unique_param_ident(&format!("Base{}", param.unraw())),
+            )
+        })
+        .collect::<Vec<_>>();
+    let value_or_base_trait_ident = from_struct.value_or_base_trait_ident();
+
+    let resolve_all_fields = from_struct.fields.iter().map(|field| {
         let field_name = field.ident();
-        match field.default_to_set() {
+        let field_local = from_struct.field_local(field);
+        match base_params
+            .iter()
+            .find(|(param, _)| Some(*param) == from_struct.generics.type_changing_param(field))
+        {
+            Some((param, base_param)) => {
+                let field_placeholder = field.field_placeholder();
+                quote! {
+                    let #field_local = <#field_placeholder as #value_or_base_trait_ident<#param, #base_param>>::value_or_base(
+                        self.#field_name,
+                        #base.#field_name,
+                    );
+                }
+            }
             None => quote! {
-                #field_name: self.#field_name.unwrap()
-            },
-            Some(DefaultToSet::AsDefault) => quote! {
-                #field_name: self.#field_name.unwrap_or_default()
-            },
-            Some(DefaultToSet::AsValue(value)) => quote! {
-                #field_name: self.#field_name.unwrap_or(#value)
+                let #field_local = match self.#field_name {
+                    ::core::option::Option::Some(value) => value,
+                    ::core::option::Option::None => #base.#field_name,
+                };
             },
         }
     });
 
-    let all_generics = from_struct.generics.all();
-    let all_generics_names1 = from_struct.generics.all_names();
-    let all_generics_names2 = all_generics_names1.clone();
+    let all_placeholder_fields = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder())
+        .collect::<Vec<_>>();
+
+    let all_generics = from_struct.generics.state_all();
+    let all_generics_names = from_struct.generics.all_names();
 
-    let where_clause = from_struct.generics.where_clause();
+    let where_clause = from_struct.generics.state_where_clause();
+
+    let all_base_params = base_params.iter().map(|(_, base_param)| base_param);
+    let build_over_predicates = type_changing_fields
+        .iter()
+        .zip(&base_params)
+        .flat_map(|((field, param), (_, base_param))| {
+            let field_placeholder = field.field_placeholder();
+            let bounds = from_struct.generics.bounds_of(param, base_param);
+            let bounds_predicate = (!bounds.is_empty()).then(|| quote! {#base_param: #(#bounds)+*});
+            bounds_predicate.into_iter().chain([
+                quote! {#field_placeholder: #value_or_base_trait_ident<#param, #base_param>},
+            ])
+        })
+        .collect::<Vec<_>>();
+    let build_over_where_clause = (!build_over_predicates.is_empty()).then(|| {
+        quote! {where #(#build_over_predicates,)*}
+    });
 
     let builder_state_ident = from_struct.builder_state_ident();
-    let from_struct_ident = from_struct.ident();
+    let base_type = replace_params(from_struct.self_type(), &base_params);
+    let built_type = replace_params(from_struct.built_type(), &base_params);
+
+    let build_over_ident = from_struct.build_over_ident();
+    let build_over_doc = format!(
+        "Builds a `{}` out of the fields that have been set, taking the other ones from `base`.",
+        from_struct.ident()
+    );
+
+    let construct_struct = construct(from_struct);
+    let into_built_type = into_built_type(from_struct);
+
     quote! {
-        impl <#(#all_generics,)*#(#all_default_placeholder_fields_types,)*> #builder_state_ident<#(#all_generics_names1,)*#(#all_not_default_set,)*> #where_clause{
-            fn build(self) -> #from_struct_ident<#(#all_generics_names2,)*> {
-                #from_struct_ident {
-                    #(#copy_all_fields,)*
-                }
+        impl <#(#all_generics,)*#(#all_placeholder_fields,)*> #builder_state_ident<#(#all_generics_names,)*#(#all_placeholder_fields,)*> #where_clause{
+            #[doc = #build_over_doc]
+            pub fn #build_over_ident<#(#all_base_params,)*>(self, #base: #base_type) -> #built_type #build_over_where_clause {
+                #(#resolve_all_fields)*
+                #construct_struct
+                #into_built_type
             }
         }
     }
 }
+
+fn construct(from_struct: &FromStruct) -> TokenStream {
+    let all_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        let field_local = from_struct.field_local(field);
+        quote! {#field_ident: #field_local}
+    });
+
+    let from_struct_ident = from_struct.ident();
+
+    let post_build = from_struct.post_build().map(|post_build| {
+        quote! {
+            let mut built = built;
+            #post_build(&mut built);
+        }
+    });
+
+    quote! {
+        let built = #from_struct_ident {
+            #(#all_fields,)*
+        };
+        #post_build
+    }
+}
+
+fn into_built_type(from_struct: &FromStruct) -> TokenStream {
+    if from_struct.build_into().is_some() {
+        quote! {::core::convert::Into::into(built)}
+    } else {
+        quote! {built}
+    }
+}
@@ -4,7 +4,9 @@ use quote::quote;
 
 pub(crate) fn create(from_struct: &FromStruct) -> TokenStream {
     let builder_factory_ident = from_struct.builder_ident();
+    let factory_attrs = from_struct.factory_attrs();
     quote! {
+        #(#[#factory_attrs])*
         struct #builder_factory_ident {
         }
     }
@@ -0,0 +1,263 @@
+use crate::parse::{Field, FromStruct, GroupRule, RelationKind};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+
+pub(crate) fn create(from_struct: &FromStruct) -> Vec<TokenStream> {
+    let required_traits = from_struct
+        .fields
+        .iter()
+        .filter(|field| !field.has_default())
+        .map(|field| {
+            state_trait(
+                &from_struct.required_trait_ident(field),
+                &[field],
+                vec![vec![Some(true)]],
+                true,
+                from_struct,
+            )
+        });
+
+    let single_traits = from_struct
+        .fields
+        .iter()
+        .filter(|field| !field.has_multi())
+        .map(|field| {
+            state_trait(
+                &from_struct.single_trait_ident(field),
+                &[field],
+                vec![vec![Some(false)]],
+                false,
+                from_struct,
+            )
+        });
+
+    let group_traits = from_struct.groups().into_iter().map(|group| {
+        state_trait(
+            &from_struct.group_trait_ident(&group),
+            &group.members,
+            group.rule.allowed_states(group.members.len()),
+            true,
+            from_struct,
+        )
+    });
+
+    let relation_traits = from_struct.relations().into_iter().map(|relation| {
+        state_trait(
+            &from_struct.relation_trait_ident(&relation),
+            &[relation.field, relation.other],
+            [(false, false), (false, true), (true, false), (true, true)]
+                .into_iter()
+                .filter(|(field_set, other_set)| relation.kind.allows(*field_set, *other_set))
+                .map(|(field_set, other_set)| vec![Some(field_set), Some(other_set)])
+                .collect(),
+            true,
+            from_struct,
+        )
+    });
+
+    required_traits
+        .chain(single_traits)
+        .chain(group_traits)
+        .chain(relation_traits)
+        .collect()
+}
+
+/// A trait implemented by the builder states in `allowed_states`, which tell for each of the
+/// `constrained_fields` whether it is set, or `None` when it doesn't matter. The trait is named
+/// after the fields, so that the compiler error for a state that isn't allowed tells which fields
+/// are involved.
+///
+/// When `allows_built` is true, the trait is also implemented by the builder states made out of
+/// a built value, which satisfied the constraint when it was built, as long as one of the
+/// `constrained_fields` is preset: the others are always marked as set there.
+///
+/// `#[diagnostic::on_unimplemented]` would give a better message, but the compiler doesn't
+/// resolve it in `#[no_implicit_prelude]` modules.
+fn state_trait(
+    trait_ident: &Ident,
+    constrained_fields: &[&Field],
+    allowed_states: Vec<Vec<Option<bool>>>,
+    allows_built: bool,
+    from_struct: &FromStruct,
+) -> TokenStream {
+    let all_generics = from_struct.generics.state_all();
+    let all_generics_names = from_struct.generics.all_names();
+    let where_clause = from_struct.generics.state_where_clause();
+    let builder_state_ident = from_struct.builder_state_ident();
+
+    let constrained_field_index = |field: &Field| {
+        constrained_fields
+            .iter()
+            .position(|constrained_field| constrained_field.ident() == field.ident())
+    };
+
+    let state_impl = |fields_markers: Vec<Option<Ident>>| {
+        let is_free = |field: &Field| {
+            constrained_field_index(field).is_none_or(|index| fields_markers[index].is_none())
+        };
+        let free_placeholder_fields_types = from_struct
+            .fields
+            .iter()
+            .filter(|field| is_free(field))
+            .map(|field| field.field_placeholder());
+        let placeholder_fields = from_struct.fields.iter().map(|field| {
+            match constrained_field_index(field).and_then(|index| fields_markers[index].as_ref()) {
+                Some(marker) => quote! {#marker},
+                None => field.field_placeholder(),
+            }
+        });
+        quote! {
+            impl<#(#all_generics,)*#(#free_placeholder_fields_types,)*> #trait_ident for #builder_state_ident<#(#all_generics_names,)*#(#placeholder_fields,)*> #where_clause {}
+        }
+    };
+
+    let allowed_states = allowed_states.into_iter().map(|fields_set| {
+        constrained_fields
+            .iter()
+            .zip(fields_set)
+            .map(|(field, is_set)| is_set.map(|is_set| from_struct.field_marker(field, is_set)))
+            .collect()
+    });
+    let built_state = (allows_built
+        && constrained_fields
+            .iter()
+            .any(|field| from_struct.is_preset(field)))
+    .then(|| {
+        constrained_fields
+            .iter()
+            .map(|field| Some(from_struct.built_marker(field)))
+            .collect()
+    });
+    let all_state_impls = allowed_states.chain(built_state).map(state_impl);
+
+    quote! {
+        trait #trait_ident {}
+
+        #(#all_state_impls)*
+    }
+}
+
+/// The predicates requiring `builder_state_type` to have all the required fields set, and to
+/// satisfy all the groups and relations.
+pub(crate) fn build_predicates(
+    from_struct: &FromStruct,
+    builder_state_type: &TokenStream,
+) -> Vec<TokenStream> {
+    let required_traits = from_struct
+        .fields
+        .iter()
+        .filter(|field| !field.has_default())
+        .map(|field| from_struct.required_trait_ident(field))
+        .collect::<Vec<_>>();
+    let group_traits = from_struct
+        .groups()
+        .iter()
+        .map(|group| from_struct.group_trait_ident(group))
+        .collect::<Vec<_>>();
+    let relation_traits = from_struct
+        .relations()
+        .iter()
+        .map(|relation| from_struct.relation_trait_ident(relation))
+        .collect::<Vec<_>>();
+
+    required_traits
+        .into_iter()
+        .chain(group_traits)
+        .chain(relation_traits)
+        .map(|trait_ident| quote! {#builder_state_type: #trait_ident})
+        .collect()
+}
+
+/// The predicate requiring `field` not to be set yet in `builder_state_type`, unless it could
+/// be set multiple times.
+pub(crate) fn setter_predicate(
+    field: &Field,
+    from_struct: &FromStruct,
+    builder_state_type: &TokenStream,
+) -> Option<TokenStream> {
+    (!field.has_multi()).then(|| {
+        let trait_ident = from_struct.single_trait_ident(field);
+        quote! {#builder_state_type: #trait_ident}
+    })
+}
+
+/// Evaluates to the `(group, rule)` pairs of the groups whose rule isn't satisfied by the
+/// fields set in `source`, whose fields are all `Option`s named after the struct fields.
+pub(crate) fn group_violations(from_struct: &FromStruct, source: &TokenStream) -> TokenStream {
+    let alloc = from_struct.alloc_crate_ident();
+    let violations = Ident::new("violations", Span::mixed_site());
+    let set_members = Ident::new("set_members", Span::mixed_site());
+
+    let check_all_groups = from_struct.groups().into_iter().map(|group| {
+        let group_name = group.name.unraw().to_string();
+        let rule_description = group.rule.description();
+        let count_set_members = group.members.iter().map(|member| {
+            let member_ident = member.ident();
+            quote! {
+                if #source.#member_ident.is_some() {
+                    #set_members += 1;
+                }
+            }
+        });
+        let rule_satisfied = match group.rule {
+            GroupRule::Exactly => quote! {#set_members == 1},
+            GroupRule::AtLeast => quote! {#set_members >= 1},
+            GroupRule::AtMost => quote! {#set_members <= 1},
+        };
+        quote! {
+            let mut #set_members = 0usize;
+            #(#count_set_members)*
+            if !(#rule_satisfied) {
+                #violations.push((#group_name, #rule_description));
+            }
+        }
+    });
+
+    quote! {
+        {
+            #[allow(unused_mut)]
+            let mut #violations: #alloc::vec::Vec<(&'static str, &'static str)> = #alloc::vec::Vec::new();
+            #(#check_all_groups)*
+            #violations
+        }
+    }
+}
+
+/// Evaluates to the `(field, relation, other field)` triples of the relations that don't hold
+/// for the fields set in `source`, whose fields are all `Option`s named after the struct fields.
+pub(crate) fn relation_violations(from_struct: &FromStruct, source: &TokenStream) -> TokenStream {
+    let alloc = from_struct.alloc_crate_ident();
+    let violations = Ident::new("violations", Span::mixed_site());
+
+    let check_all_relations = from_struct.relations().into_iter().map(|relation| {
+        let field_ident = relation.field.ident();
+        let field_name = relation.field.name();
+        let other_ident = relation.other.ident();
+        let other_name = relation.other.name();
+        let relation_description = relation.kind.description();
+        let relation_holds = match relation.kind {
+            RelationKind::Requires => {
+                quote! {#source.#field_ident.is_none() || #source.#other_ident.is_some()}
+            }
+            RelationKind::ConflictsWith => {
+                quote! {#source.#field_ident.is_none() || #source.#other_ident.is_none()}
+            }
+        };
+        quote! {
+            if !(#relation_holds) {
+                #violations.push((#field_name, #relation_description, #other_name));
+            }
+        }
+    });
+
+    quote! {
+        {
+            #[allow(unused_mut)]
+            let mut #violations: #alloc::vec::Vec<(&'static str, &'static str, &'static str)> =
+                #alloc::vec::Vec::new();
+            #(#check_all_relations)*
+            #violations
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::parse::FromStruct;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub(crate) fn create(from_struct: &FromStruct) -> Option<TokenStream> {
+    if !from_struct.has_debug() {
+        return None;
+    }
+
+    let field_state_trait_ident = from_struct.field_state_trait_ident();
+
+    let debug_all_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        let field_name = field.name();
+        let field_placeholder = field.field_placeholder();
+
+        let set_value = if field.is_debug_redacted() {
+            quote! {&::core::format_args!("<redacted>")}
+        } else {
+            quote! {value}
+        };
+
+        let unset_value = match field.default_to_set() {
+            None => "<unset>".to_string(),
+            Some(default_to_set) => match default_to_set.source_text() {
+                Some(source_text) => format!("<default: {source_text}>"),
+                None => "<default>".to_string(),
+            },
+        };
+
+        quote! {
+            match &self.#field_ident {
+                ::core::option::Option::Some(value) if <#field_placeholder as #field_state_trait_ident>::IS_SET => {
+                    debug.field(#field_name, #set_value)
+                }
+                _ => debug.field(#field_name, &::core::format_args!("{}", #unset_value)),
+            };
+        }
+    });
+
+    let debug_field_types = from_struct.fields.iter().filter_map(|field| {
+        if field.is_debug_redacted() {
+            None
+        } else {
+            let field_type = field.ty();
+            Some(quote! {#field_type: ::core::fmt::Debug})
+        }
+    });
+
+    let all_placeholder_fields_types = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder());
+
+    let all_placeholder_fields = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder());
+
+    let all_generics = from_struct.generics.state_all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let placeholder_bounds = from_struct.fields.iter().map(|field| {
+        let field_placeholder = field.field_placeholder();
+        quote! {#field_placeholder: #field_state_trait_ident}
+    });
+
+    let where_clause = from_struct
+        .generics
+        .state_where_clause_with(debug_field_types.chain(placeholder_bounds).collect());
+
+    let builder_state_ident = from_struct.builder_state_ident();
+    let from_struct_name = from_struct.ident().to_string();
+
+    Some(quote! {
+        impl<#(#all_generics,)*#(#all_placeholder_fields_types,)*> ::core::fmt::Debug for #builder_state_ident<#(#all_generics_names,)*#(#all_placeholder_fields,)*> #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                let mut debug = f.debug_struct(#from_struct_name);
+                #(#debug_all_fields)*
+                debug.finish()
+            }
+        }
+    })
+}
@@ -0,0 +1,358 @@
+use crate::generators::{build, constraints};
+use crate::parse::{Field, FromStruct};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{ToTokens, quote};
+
+pub(crate) fn create(from_struct: &FromStruct) -> Option<TokenStream> {
+    if !from_struct.is_dynamic() {
+        return None;
+    }
+
+    let dyn_fields_declarations = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        let field_type = field.ty();
+        quote! {
+            #field_ident: ::core::option::Option<#field_type>
+        }
+    });
+
+    let all_unset_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        quote! {
+            #field_ident: ::core::option::Option::None
+        }
+    });
+
+    let copy_all_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        quote! {
+            #field_ident: value.#field_ident
+        }
+    });
+
+    let all_setters = from_struct
+        .fields
+        .iter()
+        .map(|field| setter_for(field, from_struct));
+
+    let missing_fields = Ident::new("missing_fields", Span::mixed_site());
+
+    let check_required_fields = from_struct.fields.iter().filter_map(|field| {
+        if field.has_default() {
+            None
+        } else {
+            let field_ident = field.ident();
+            let field_name = field.name();
+            Some(quote! {
+                if self.#field_ident.is_none() {
+                    #missing_fields.push(#field_name);
+                }
+            })
+        }
+    });
+
+    let all_placeholder_fields_types = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder());
+
+    let all_placeholder_fields = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder());
+
+    let all_generics_declarations = from_struct.generics.declarations();
+    let all_generics = from_struct.generics.all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let where_clause = from_struct.generics.where_clause();
+    let build_where_clause = from_struct
+        .generics
+        .where_clause_with(build::predicates(from_struct));
+
+    let dyn_builder_ident = from_struct.dyn_builder_ident();
+    let dyn_builder_error_ident = from_struct.dyn_builder_error_ident();
+    let alloc = from_struct.alloc_crate_ident();
+    let vis = &from_struct.vis;
+    let dyn_builder_doc = format!(
+        "A builder of `{}` whose fields are checked when it is built.",
+        from_struct.ident()
+    );
+    let dyn_builder_error_doc = format!("The reason a `{dyn_builder_ident}` can't be built.");
+    let builder_state_ident = from_struct.builder_state_ident();
+    let builder_state_type = quote! {
+        #builder_state_ident<#(#all_generics_names,)*#(#all_placeholder_fields,)*>
+    };
+    let built_type = from_struct.built_type();
+    let build_body = build::body(from_struct, &quote! {self});
+    let group_violations = constraints::group_violations(from_struct, &quote! {self});
+    let relation_violations = constraints::relation_violations(from_struct, &quote! {self});
+
+    let set_by_name = from_struct
+        .is_from_pairs()
+        .then(|| set_by_name(from_struct));
+
+    let from_pairs = from_struct.is_from_pairs().then(|| from_pairs(from_struct));
+
+    Some(quote! {
+        #[doc = #dyn_builder_doc]
+        #vis struct #dyn_builder_ident<#(#all_generics_declarations,)*> #where_clause {
+            #(#dyn_fields_declarations,)*
+        }
+
+        #[derive(
+            ::core::fmt::Debug,
+            ::core::clone::Clone,
+            ::core::cmp::PartialEq,
+            ::core::cmp::Eq
+        )]
+        #[doc = #dyn_builder_error_doc]
+        #vis enum #dyn_builder_error_ident {
+            /// Required fields aren't set.
+            MissingFields(#alloc::vec::Vec<&'static str>),
+            /// A field that can only be set once is set again.
+            AlreadySet(&'static str),
+            /// No field has the given name.
+            UnknownField(#alloc::string::String),
+            /// The value of a field can't be parsed.
+            InvalidValue {
+                /// The name of the field.
+                field: &'static str,
+                /// Why the value can't be parsed.
+                message: #alloc::string::String,
+            },
+            /// The rule of a group isn't satisfied.
+            GroupRuleViolated {
+                /// The name of the group.
+                group: &'static str,
+                /// The rule of the group.
+                rule: &'static str,
+            },
+            /// A field requires a field that isn't set, or conflicts with a field that is set.
+            RelationViolated {
+                /// The name of the field.
+                field: &'static str,
+                /// Whether the field requires or conflicts with the other field.
+                relation: &'static str,
+                /// The name of the other field.
+                other: &'static str,
+            },
+        }
+
+        impl ::core::fmt::Display for #dyn_builder_error_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match self {
+                    #dyn_builder_error_ident::MissingFields(fields) => {
+                        ::core::write!(f, "missing required fields: {}", fields.join(", "))
+                    }
+                    #dyn_builder_error_ident::AlreadySet(field) => {
+                        ::core::write!(f, "field {} has already been set", field)
+                    }
+                    #dyn_builder_error_ident::UnknownField(field) => {
+                        ::core::write!(f, "unknown field {}", field)
+                    }
+                    #dyn_builder_error_ident::InvalidValue { field, message } => {
+                        ::core::write!(f, "invalid value for field {}: {}", field, message)
+                    }
+                    #dyn_builder_error_ident::GroupRuleViolated { group, rule } => {
+                        ::core::write!(f, "{} field of group {} must be set", rule, group)
+                    }
+                    #dyn_builder_error_ident::RelationViolated { field, relation, other } => {
+                        ::core::write!(f, "field {} {} field {}", field, relation, other)
+                    }
+                }
+            }
+        }
+
+        impl ::core::error::Error for #dyn_builder_error_ident {}
+
+        impl<#(#all_generics,)*> ::core::default::Default for #dyn_builder_ident<#(#all_generics_names,)*> #where_clause {
+            fn default() -> Self {
+                #dyn_builder_ident {
+                    #(#all_unset_fields,)*
+                }
+            }
+        }
+
+        impl<#(#all_generics,)*> #dyn_builder_ident<#(#all_generics_names,)*> #where_clause {
+            /// A builder with no field set.
+            pub fn new() -> Self {
+                ::core::default::Default::default()
+            }
+
+            #(#all_setters)*
+
+            #set_by_name
+
+            /// Builds the struct, unless required fields aren't set, or groups or relations
+            /// between fields aren't satisfied.
+            pub fn try_build(self) -> ::core::result::Result<#built_type, #dyn_builder_error_ident> #build_where_clause {
+                let mut #missing_fields = #alloc::vec::Vec::new();
+                #(#check_required_fields)*
+                if !#missing_fields.is_empty() {
+                    return ::core::result::Result::Err(#dyn_builder_error_ident::MissingFields(#missing_fields));
+                }
+                if let ::core::option::Option::Some(&(group, rule)) = #group_violations.first() {
+                    return ::core::result::Result::Err(#dyn_builder_error_ident::GroupRuleViolated { group, rule });
+                }
+                if let ::core::option::Option::Some(&(field, relation, other)) =
+                    #relation_violations.first() {
+                    return ::core::result::Result::Err(#dyn_builder_error_ident::RelationViolated { field, relation, other });
+                }
+                ::core::result::Result::Ok({
+                    #build_body
+                })
+            }
+        }
+
+        impl<#(#all_generics,)*#(#all_placeholder_fields_types,)*> ::core::convert::From<#builder_state_type> for #dyn_builder_ident<#(#all_generics_names,)*> #where_clause {
+            fn from(value: #builder_state_type) -> Self {
+                #dyn_builder_ident {
+                    #(#copy_all_fields,)*
+                }
+            }
+        }
+
+        #from_pairs
+    })
+}
+
+fn set_by_name(from_struct: &FromStruct) -> TokenStream {
+    let dyn_builder_error_ident = from_struct.dyn_builder_error_ident();
+    let alloc = from_struct.alloc_crate_ident();
+
+    let set_all_fields = from_struct
+        .fields
+        .iter()
+        .filter(|field| !field.is_skip_pairs())
+        .map(|field| {
+            let field_name = field.name();
+            let setter_ident = field
+                .setter_ident()
+                .unwrap_or(field.ident().to_token_stream());
+            let setter_name = field.setter_name();
+            let setter_name = (setter_name != field_name)
+                .then_some(setter_name)
+                .into_iter();
+            let field_type = field.ty();
+            let set_value = if field.has_multi() {
+                quote! {self.#setter_ident(value);}
+            } else {
+                quote! {self.#setter_ident(value)?;}
+            };
+
+            quote! {
+                #field_name #(| #setter_name)* => {
+                    let value = value.parse::<#field_type>().map_err(|error| {
+                        #dyn_builder_error_ident::InvalidValue {
+                            field: #field_name,
+                            message: #alloc::string::ToString::to_string(&error),
+                        }
+                    })?;
+                    #set_value
+                    ::core::result::Result::Ok(())
+                }
+            }
+        });
+
+    let where_clause = from_struct
+        .generics
+        .where_clause_with(parsable_predicates(from_struct));
+
+    quote! {
+        /// Sets the field with the given field or setter name to the value parsed from `value`.
+        pub fn set_by_name(&mut self, name: &str, value: &str) -> ::core::result::Result<(), #dyn_builder_error_ident> #where_clause {
+            match name {
+                #(#set_all_fields)*
+                _ => ::core::result::Result::Err(#dyn_builder_error_ident::UnknownField(
+                    #alloc::string::ToString::to_string(name),
+                )),
+            }
+        }
+    }
+}
+
+fn from_pairs(from_struct: &FromStruct) -> TokenStream {
+    let all_generics = from_struct.generics.all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let where_clause = from_struct.generics.where_clause_with(
+        parsable_predicates(from_struct)
+            .into_iter()
+            .chain(build::predicates(from_struct))
+            .collect(),
+    );
+
+    let builder_factory_ident = from_struct.builder_ident();
+    let dyn_builder_ident = from_struct.dyn_builder_ident();
+    let dyn_builder_error_ident = from_struct.dyn_builder_error_ident();
+    let built_type = from_struct.built_type();
+
+    quote! {
+        impl #builder_factory_ident {
+            /// Builds the struct out of the fields named in `pairs`, set to the values parsed from
+            /// their strings.
+            pub fn from_pairs<#(#all_generics,)*>(
+                pairs: impl ::core::iter::IntoIterator<
+                    Item = (impl ::core::convert::AsRef<str>, impl ::core::convert::AsRef<str>),
+                >,
+            ) -> ::core::result::Result<#built_type, #dyn_builder_error_ident> #where_clause {
+                let mut builder = #dyn_builder_ident::<#(#all_generics_names,)*>::new();
+                for (name, value) in pairs {
+                    builder.set_by_name(
+                        ::core::convert::AsRef::as_ref(&name),
+                        ::core::convert::AsRef::as_ref(&value),
+                    )?;
+                }
+                builder.try_build()
+            }
+        }
+    }
+}
+
+fn parsable_predicates(from_struct: &FromStruct) -> Vec<TokenStream> {
+    from_struct
+        .fields
+        .iter()
+        .filter(|field| !field.is_skip_pairs())
+        .map(|field| {
+            let field_type = field.ty();
+            quote! {
+                #field_type: ::core::str::FromStr,
+                <#field_type as ::core::str::FromStr>::Err: ::core::fmt::Display
+            }
+        })
+        .collect()
+}
+
+fn setter_for(field: &Field, from_struct: &FromStruct) -> TokenStream {
+    let field_ident = field.ident();
+    let field_name = field.name();
+    let setter_ident = field
+        .setter_ident()
+        .unwrap_or(field.ident().to_token_stream());
+    let field_type = field.ty();
+    let dyn_builder_error_ident = from_struct.dyn_builder_error_ident();
+    let setter_doc = format!("Sets the field `{field_name}`.");
+
+    if field.has_multi() {
+        quote! {
+            #[doc = #setter_doc]
+            pub fn #setter_ident(&mut self, value: #field_type) -> &mut Self {
+                self.#field_ident = ::core::option::Option::Some(value);
+                self
+            }
+        }
+    } else {
+        quote! {
+            #[doc = #setter_doc]
+            pub fn #setter_ident(&mut self, value: #field_type) -> ::core::result::Result<&mut Self, #dyn_builder_error_ident> {
+                if self.#field_ident.is_some() {
+                    return ::core::result::Result::Err(#dyn_builder_error_ident::AlreadySet(#field_name));
+                }
+                self.#field_ident = ::core::option::Option::Some(value);
+                ::core::result::Result::Ok(self)
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::generators::{build, constraints};
+use crate::parse::FromStruct;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+
+pub(crate) fn create(from_struct: &FromStruct) -> Option<TokenStream> {
+    if !from_struct.is_from_env() {
+        return None;
+    }
+
+    let builder = Ident::new("builder", Span::mixed_site());
+    let invalid_fields = Ident::new("invalid_fields", Span::mixed_site());
+    let missing_fields = Ident::new("missing_fields", Span::mixed_site());
+    let violated_groups = Ident::new("violated_groups", Span::mixed_site());
+    let violated_relations = Ident::new("violated_relations", Span::mixed_site());
+    let alloc = from_struct.alloc_crate_ident();
+
+    let env_fields = from_struct
+        .fields
+        .iter()
+        .filter_map(|field| from_struct.env_var(field).map(|env_var| (field, env_var)))
+        .collect::<Vec<_>>();
+
+    let load_all_env_fields = env_fields.iter().map(|(field, env_var)| {
+        let field_ident = field.ident();
+        let field_name = field.name();
+        let field_type = field.ty();
+        quote! {
+            if let ::core::option::Option::Some(value) = lookup(#env_var) {
+                match value.parse::<#field_type>() {
+                    ::core::result::Result::Ok(value) => {
+                        #builder.#field_ident = ::core::option::Option::Some(value)
+                    }
+                    ::core::result::Result::Err(error) => #invalid_fields
+                        .push((#field_name, #alloc::string::ToString::to_string(&error))),
+                }
+            }
+        }
+    });
+
+    let check_required_fields = from_struct.fields.iter().filter_map(|field| {
+        if field.has_default() {
+            None
+        } else {
+            let field_ident = field.ident();
+            let field_name = field.name();
+            Some(quote! {
+                if #builder.#field_ident.is_none()
+                    && !::core::iter::Iterator::any(&mut #invalid_fields.iter(), |(field, _)| {
+                        *field == #field_name
+                    })
+                {
+                    #missing_fields.push(#field_name);
+                }
+            })
+        }
+    });
+
+    let parsable_field_types = env_fields.iter().map(|(field, _)| {
+        let field_type = field.ty();
+        quote! {
+            #field_type: ::core::str::FromStr,
+            <#field_type as ::core::str::FromStr>::Err: ::core::fmt::Display
+        }
+    });
+
+    let all_generics = from_struct.generics.all();
+    let all_generics_names1 = from_struct.generics.all_names();
+    let all_generics_names2 = all_generics_names1.clone();
+
+    let where_clause = from_struct.generics.where_clause_with(
+        parsable_field_types
+            .chain(build::predicates(from_struct))
+            .collect(),
+    );
+
+    let builder_factory_ident = from_struct.builder_ident();
+    let dyn_builder_ident = from_struct.dyn_builder_ident();
+    let env_error_ident = from_struct.env_error_ident();
+    let vis = &from_struct.vis;
+    let env_error_doc = format!(
+        "The reasons a `{}` can't be loaded from the environment.",
+        from_struct.ident()
+    );
+    let built_type = from_struct.built_type();
+    let build_body = build::body(from_struct, &quote! {#builder});
+    let group_violations = constraints::group_violations(from_struct, &quote! {#builder});
+    let relation_violations = constraints::relation_violations(from_struct, &quote! {#builder});
+
+    Some(quote! {
+        #[derive(
+            ::core::fmt::Debug,
+            ::core::clone::Clone,
+            ::core::cmp::PartialEq,
+            ::core::cmp::Eq
+        )]
+        #[doc = #env_error_doc]
+        #vis struct #env_error_ident {
+            /// The required fields whose variable isn't set.
+            pub missing_fields: #alloc::vec::Vec<&'static str>,
+            /// The fields whose value can't be parsed, with the reason.
+            pub invalid_fields: #alloc::vec::Vec<(&'static str, #alloc::string::String)>,
+            /// The groups whose rule isn't satisfied, with the rule.
+            pub violated_groups: #alloc::vec::Vec<(&'static str, &'static str)>,
+            /// The relations between fields that don't hold.
+            pub violated_relations: #alloc::vec::Vec<(&'static str, &'static str, &'static str)>,
+        }
+
+        impl ::core::fmt::Display for #env_error_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                let mut errors = #alloc::vec::Vec::new();
+                if !self.missing_fields.is_empty() {
+                    errors.push(#alloc::format!("missing required fields: {}", self.missing_fields.join(", ")));
+                }
+                for (field, message) in &self.invalid_fields {
+                    errors.push(#alloc::format!("invalid value for field {}: {}", field, message));
+                }
+                for (group, rule) in &self.violated_groups {
+                    errors.push(#alloc::format!("{} field of group {} must be set", rule, group));
+                }
+                for (field, relation, other) in &self.violated_relations {
+                    errors.push(#alloc::format!("field {} {} field {}", field, relation, other));
+                }
+                ::core::write!(f, "{}", errors.join("; "))
+            }
+        }
+
+        impl ::core::error::Error for #env_error_ident {}
+
+        impl #builder_factory_ident {
+            /// Builds the struct out of the environment variables.
+            pub fn from_env<#(#all_generics,)*>() -> ::core::result::Result<#built_type, #env_error_ident> #where_clause {
+                Self::from_env_with::<#(#all_generics_names1,)*>(|name| ::std::env::var(name).ok())
+            }
+
+            /// Builds the struct out of the variables given by `lookup`.
+            pub fn from_env_with<#(#all_generics,)*>(
+                lookup: impl ::core::ops::Fn(&str) -> ::core::option::Option<#alloc::string::String>,
+            ) -> ::core::result::Result<#built_type, #env_error_ident> #where_clause {
+                let mut #builder = #dyn_builder_ident::<#(#all_generics_names2,)*>::new();
+                let mut #invalid_fields = #alloc::vec::Vec::new();
+                #(#load_all_env_fields)*
+
+                let mut #missing_fields = #alloc::vec::Vec::new();
+                #(#check_required_fields)*
+                let #violated_groups = #group_violations;
+                let #violated_relations = #relation_violations;
+                if !#missing_fields.is_empty()
+                    || !#invalid_fields.is_empty()
+                    || !#violated_groups.is_empty()
+                    || !#violated_relations.is_empty()
+                {
+                    return ::core::result::Result::Err(#env_error_ident {
+                        missing_fields: #missing_fields,
+                        invalid_fields: #invalid_fields,
+                        violated_groups: #violated_groups,
+                        violated_relations: #violated_relations,
+                    });
+                }
+
+                ::core::result::Result::Ok({
+                    #build_body
+                })
+            }
+        }
+    })
+}
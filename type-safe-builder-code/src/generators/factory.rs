@@ -7,29 +7,30 @@ pub(crate) fn create(from_struct: &FromStruct) -> TokenStream {
         let field_ident = &field.ident();
 
         quote! {
-            #field_ident: None
+            #field_ident: ::core::option::Option::None
         }
     });
 
-    let all_unset = from_struct.fields.iter().map(|_| {
-        quote! {
-            false
-        }
-    });
+    let all_unset = from_struct
+        .fields
+        .iter()
+        .map(|field| from_struct.field_marker(field, false));
 
-    let all_generics = from_struct.generics.all();
-    let all_generics_names = from_struct.generics.all_names();
+    let fixed_generics = from_struct.generics.fixed_all();
+    let initial_generics_names = from_struct.generics.initial_names();
 
-    let where_clause = from_struct.generics.where_clause();
+    let where_clause = from_struct.generics.state_where_clause();
 
     let builder_factory_ident = from_struct.builder_ident();
     let builder_state_ident = from_struct.builder_state_ident();
+    let markers_field_ident = from_struct.markers_field_ident();
 
     quote! {
         impl #builder_factory_ident {
-            pub fn builder<#(#all_generics,)*>() -> #builder_state_ident<#(#all_generics_names,)*#(#all_unset,)*> #where_clause {
+            pub fn builder<#(#fixed_generics,)*>() -> #builder_state_ident<#(#initial_generics_names,)*#(#all_unset,)*> #where_clause {
                 #builder_state_ident {
                     #(#all_unset_fields,)*
+                    #markers_field_ident: ::core::marker::PhantomData,
                 }
             }
         }
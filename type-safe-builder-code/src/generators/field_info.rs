@@ -0,0 +1,134 @@
+use crate::parse::FromStruct;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub(crate) fn create(from_struct: &FromStruct) -> TokenStream {
+    let field_info_ident = from_struct.field_info_ident();
+
+    let all_fields_info = from_struct.fields.iter().map(|field| {
+        let name = field.name();
+        let setter_name = field.setter_name();
+        let type_name = field.type_name();
+        let default_to_set = field.default_to_set();
+        let required = default_to_set.is_none();
+        let multi = field.has_multi();
+        let default_value = match default_to_set.and_then(|default| default.source_text()) {
+            Some(source_text) => quote! {::core::option::Option::Some(#source_text)},
+            None => quote! {::core::option::Option::None},
+        };
+
+        quote! {
+            #field_info_ident {
+                name: #name,
+                setter_name: #setter_name,
+                type_name: #type_name,
+                required: #required,
+                multi: #multi,
+                default_value: #default_value,
+            }
+        }
+    });
+
+    let field_state_trait_ident = from_struct.field_state_trait_ident();
+
+    let is_set_all_fields = from_struct.fields.iter().map(|field| {
+        let name = field.name();
+        let field_placeholder = field.field_placeholder();
+        quote! {
+            if eq(name, #name) {
+                return <#field_placeholder as #field_state_trait_ident>::IS_SET;
+            }
+        }
+    });
+
+    let all_placeholder_fields_types = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder());
+
+    let all_placeholder_fields = from_struct
+        .fields
+        .iter()
+        .map(|field| field.field_placeholder());
+
+    let all_generics = from_struct.generics.state_all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let placeholder_bounds = from_struct
+        .fields
+        .iter()
+        .map(|field| {
+            let field_placeholder = field.field_placeholder();
+            quote! {#field_placeholder: #field_state_trait_ident}
+        })
+        .collect();
+
+    let where_clause = from_struct
+        .generics
+        .state_where_clause_with(placeholder_bounds);
+
+    let builder_factory_ident = from_struct.builder_ident();
+    let builder_state_ident = from_struct.builder_state_ident();
+    let is_set_ident = from_struct.is_set_ident();
+    let vis = &from_struct.vis;
+    let field_info_doc = format!("Information about a field of `{}`.", from_struct.ident());
+    let fields_doc = format!(
+        "The fields of `{}`, in declaration order.",
+        from_struct.ident()
+    );
+
+    quote! {
+        #[derive(
+            ::core::fmt::Debug,
+            ::core::clone::Clone,
+            ::core::marker::Copy,
+            ::core::cmp::PartialEq,
+            ::core::cmp::Eq
+        )]
+        #[doc = #field_info_doc]
+        #vis struct #field_info_ident {
+            /// The name of the field.
+            pub name: &'static str,
+            /// The name of the setter of the field.
+            pub setter_name: &'static str,
+            /// The type of the field, as written in the struct.
+            pub type_name: &'static str,
+            /// Whether the field must be set.
+            pub required: bool,
+            /// Whether the field can be set multiple times.
+            pub multi: bool,
+            /// The default value of the field, as written in the struct.
+            pub default_value: ::core::option::Option<&'static str>,
+        }
+
+        impl #builder_factory_ident {
+            #[doc = #fields_doc]
+            pub const FIELDS: &'static [#field_info_ident] = &[
+                #(#all_fields_info,)*
+            ];
+        }
+
+        impl<#(#all_generics,)*#(#all_placeholder_fields_types,)*> #builder_state_ident<#(#all_generics_names,)*#(#all_placeholder_fields,)*> #where_clause {
+            /// Whether the field named `name` is set, `false` for unknown fields.
+            pub const fn #is_set_ident(&self, name: &str) -> bool {
+                const fn eq(a: &str, b: &str) -> bool {
+                    let (a, b) = (a.as_bytes(), b.as_bytes());
+                    if a.len() != b.len() {
+                        return false;
+                    }
+                    let mut i = 0;
+                    while i < a.len() {
+                        if a[i] != b[i] {
+                            return false;
+                        }
+                        i += 1;
+                    }
+                    true
+                }
+
+                #(#is_set_all_fields)*
+                false
+            }
+        }
+    }
+}
@@ -1,5 +1,14 @@
 pub mod all_field_setters;
+pub mod alloc_crate;
 pub mod build;
 pub mod builder_struct;
+pub mod constraints;
+pub mod debug;
+pub mod dynamic;
+pub mod env;
 pub mod factory;
+pub mod field_info;
+pub mod partial;
+pub mod serde;
 pub mod state_struct;
+pub mod to_builder;
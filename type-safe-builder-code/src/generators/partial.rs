@@ -0,0 +1,104 @@
+use crate::generators::build;
+use crate::parse::FromStruct;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub(crate) fn create(from_struct: &FromStruct) -> Option<TokenStream> {
+    if !from_struct.is_partial() {
+        return None;
+    }
+
+    let partial_fields_declarations = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        let field_type = field.ty();
+        let field_doc = format!("The field `{}`, if it is set.", field.name());
+        quote! {
+            #[doc = #field_doc]
+            pub #field_ident: ::core::option::Option<#field_type>
+        }
+    });
+
+    let all_unset_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        quote! {
+            #field_ident: ::core::option::Option::None
+        }
+    });
+
+    let merge_all_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        quote! {
+            #field_ident: higher_priority.#field_ident.or(self.#field_ident)
+        }
+    });
+
+    let copy_all_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        quote! {
+            #field_ident: self.#field_ident
+        }
+    });
+
+    let all_generics_declarations = from_struct.generics.declarations();
+    let all_generics = from_struct.generics.all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let where_clause = from_struct.generics.where_clause();
+    let build_where_clause = from_struct
+        .generics
+        .where_clause_with(build::predicates(from_struct));
+
+    let partial_ident = from_struct.partial_ident();
+    let partial_attrs = from_struct.partial_attrs();
+    let dyn_builder_ident = from_struct.dyn_builder_ident();
+    let dyn_builder_error_ident = from_struct.dyn_builder_error_ident();
+    let built_type = from_struct.built_type();
+    let vis = &from_struct.vis;
+    let partial_doc = format!(
+        "A `{}` whose fields may not be set, which could be merged with others.",
+        from_struct.ident()
+    );
+
+    Some(quote! {
+        #[doc = #partial_doc]
+        #(#[#partial_attrs])*
+        #vis struct #partial_ident<#(#all_generics_declarations,)*> #where_clause {
+            #(#partial_fields_declarations,)*
+        }
+
+        impl<#(#all_generics,)*> ::core::default::Default for #partial_ident<#(#all_generics_names,)*> #where_clause {
+            fn default() -> Self {
+                #partial_ident {
+                    #(#all_unset_fields,)*
+                }
+            }
+        }
+
+        impl<#(#all_generics,)*> #partial_ident<#(#all_generics_names,)*> #where_clause {
+            /// A value with no field set.
+            pub fn new() -> Self {
+                ::core::default::Default::default()
+            }
+
+            /// Merges the fields of `higher_priority` over the fields of this value.
+            pub fn merge(self, higher_priority: Self) -> Self {
+                #partial_ident {
+                    #(#merge_all_fields,)*
+                }
+            }
+
+            /// A dynamic builder with the fields of this value set.
+            pub fn into_builder(self) -> #dyn_builder_ident<#(#all_generics_names,)*> {
+                #dyn_builder_ident {
+                    #(#copy_all_fields,)*
+                }
+            }
+
+            /// Builds the struct, unless required fields aren't set, or groups or relations
+            /// between fields aren't satisfied.
+            pub fn try_build(self) -> ::core::result::Result<#built_type, #dyn_builder_error_ident> #build_where_clause {
+                self.into_builder().try_build()
+            }
+        }
+    })
+}
@@ -0,0 +1,107 @@
+use crate::generators::{build, constraints};
+use crate::parse::FromStruct;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+
+pub(crate) fn create(from_struct: &FromStruct) -> Option<TokenStream> {
+    if !from_struct.is_serde() {
+        return None;
+    }
+    if !cfg!(feature = "serde") {
+        panic!("#[builder(serde)] requires the `serde` feature of type-safe-builder-macro");
+    }
+
+    let fields_ident = Ident::new("Fields", Span::mixed_site());
+    let fields = Ident::new("fields", Span::mixed_site());
+
+    let fields_declarations = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+        let field_type = field.ty();
+        let serde_attrs = field.serde_attrs();
+        let missing_behaviour = if field.has_default() {
+            quote! {#[serde(default)]}
+        } else {
+            quote! {#[serde(deserialize_with = "deserialize_required")]}
+        };
+        quote! {
+            #missing_behaviour
+            #(#serde_attrs)*
+            #field_ident: ::core::option::Option<#field_type>
+        }
+    });
+
+    let deserialize_bounds = from_struct
+        .generics
+        .type_names()
+        .into_iter()
+        .map(|type_name| quote! {#type_name: ::serde::Deserialize<'de>})
+        .collect::<Vec<_>>();
+    let serde_bound = deserialize_bounds
+        .iter()
+        .map(|bound| bound.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let all_generics_declarations = from_struct.generics.declarations();
+    let all_generics = from_struct.generics.all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let where_clause = from_struct.generics.where_clause();
+    let deserialize_where_clause = from_struct.generics.where_clause_with(
+        deserialize_bounds
+            .into_iter()
+            .chain(build::predicates(from_struct))
+            .collect(),
+    );
+
+    let struct_name = from_struct.ident().to_string();
+    let struct_serde_attrs = from_struct.serde_attrs();
+    let self_type = from_struct.self_type();
+    let struct_body = build::struct_body(from_struct, &quote! {#fields});
+    let group_violations = constraints::group_violations(from_struct, &quote! {#fields});
+    let relation_violations = constraints::relation_violations(from_struct, &quote! {#fields});
+
+    Some(quote! {
+        const _: () = {
+            #[derive(::serde::Deserialize)]
+            #[serde(rename = #struct_name, bound(deserialize = #serde_bound))]
+            #(#struct_serde_attrs)*
+            struct #fields_ident<#(#all_generics_declarations,)*> #where_clause {
+                #(#fields_declarations,)*
+            }
+
+            fn deserialize_required<'de, D, T>(deserializer: D) -> ::core::result::Result<::core::option::Option<T>, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+                T: ::serde::Deserialize<'de>,
+            {
+                <T as ::serde::Deserialize<'de>>::deserialize(deserializer).map(::core::option::Option::Some)
+            }
+
+            impl<'de, #(#all_generics,)*> ::serde::Deserialize<'de> for #self_type #deserialize_where_clause {
+                fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                where
+                    D: ::serde::Deserializer<'de>,
+                {
+                    let #fields = <#fields_ident<#(#all_generics_names,)*> as ::serde::Deserialize<'de>>::deserialize(deserializer)?;
+                    if let ::core::option::Option::Some(&(group, rule)) = #group_violations.first() {
+                        return ::core::result::Result::Err(<D::Error as ::serde::de::Error>::custom(::core::format_args!(
+                            "{} field of group {} must be set",
+                            rule, group
+                        )));
+                    }
+                    if let ::core::option::Option::Some(&(field, relation, other)) =
+                        #relation_violations.first() {
+                        return ::core::result::Result::Err(<D::Error as ::serde::de::Error>::custom(::core::format_args!(
+                            "field {} {} field {}",
+                            field, relation, other
+                        )));
+                    }
+                    ::core::result::Result::Ok({
+                        #struct_body
+                    })
+                }
+            }
+        };
+    })
+}
@@ -7,24 +7,159 @@ pub(crate) fn create(from_struct: &FromStruct) -> TokenStream {
         let field_ident = field.ident();
         let field_type = field.ty();
         quote! {
-            #field_ident: Option<#field_type>
+            #field_ident: ::core::option::Option<#field_type>
         }
     });
 
-    let all_placeholder_fields_types = from_struct
+    // Parameters following parameters with a default value must have one too.
+    let all_placeholder_fields_types = from_struct.fields.iter().map(|field| {
+        let field_placeholder = field.field_placeholder();
+        let unset_marker = from_struct.field_marker(field, false);
+        let placeholder_default = from_struct
+            .generics
+            .has_defaults()
+            .then(|| quote! {= #unset_marker});
+        quote! {#field_placeholder #placeholder_default}
+    });
+
+    let all_placeholder_fields = from_struct
         .fields
         .iter()
-        .map(|field| field.const_field_placeholder());
+        .map(|field| field.field_placeholder());
 
-    let all_generics = from_struct.generics.all();
+    let all_generics = from_struct.generics.state_declarations();
 
-    let where_clause = from_struct.generics.where_clause();
+    let where_clause = from_struct.generics.state_where_clause();
 
     let builder_state_ident = from_struct.builder_state_ident();
+    let markers_field_ident = from_struct.markers_field_ident();
+
+    let state_attrs = from_struct.state_attrs();
+    let vis = &from_struct.vis;
+    let state_doc = format!("The state of a builder of `{}`.", from_struct.ident());
+
+    let markers = markers(from_struct);
+    let state_aliases = state_aliases(from_struct);
 
     quote! {
-        struct #builder_state_ident<#(#all_generics,)*#(#all_placeholder_fields_types,)*> #where_clause {
+        #[doc = #state_doc]
+        #(#[#state_attrs])*
+        #vis struct #builder_state_ident<#(#all_generics,)*#(#all_placeholder_fields_types,)*> #where_clause {
             #(#state_fields_declarations,)*
+            #markers_field_ident: ::core::marker::PhantomData<(#(#all_placeholder_fields,)*)>,
+        }
+
+        #markers
+
+        #state_aliases
+    }
+}
+
+/// The marker types the builder state is made of, telling for each field whether it is set.
+fn markers(from_struct: &FromStruct) -> TokenStream {
+    let field_state_trait_ident = from_struct.field_state_trait_ident();
+    let vis = &from_struct.vis;
+
+    let all_markers = from_struct.fields.iter().flat_map(|field| {
+        let preset = from_struct.is_preset(field).then(|| {
+            (
+                from_struct.preset_marker(field),
+                true,
+                "set to the value of a built struct",
+            )
+        });
+        [
+            Some((from_struct.field_marker(field, true), true, "set")),
+            Some((from_struct.field_marker(field, false), false, "unset")),
+            preset,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(marker, is_set, state)| {
+            let marker_doc = format!(
+                "Marks the field `{}` of a builder state as {state}.",
+                field.name()
+            );
+            quote! {
+                #[doc = #marker_doc]
+                #[derive(
+                    ::core::fmt::Debug,
+                    ::core::clone::Clone,
+                    ::core::marker::Copy,
+                    ::core::default::Default,
+                    ::core::cmp::PartialEq,
+                    ::core::cmp::Eq,
+                    ::core::cmp::PartialOrd,
+                    ::core::cmp::Ord,
+                    ::core::hash::Hash
+                )]
+                #vis struct #marker;
+
+                impl #field_state_trait_ident for #marker {
+                    const IS_SET: bool = #is_set;
+                }
+            }
+        })
+    });
+
+    quote! {
+        /// Tells whether a field of a builder state is set.
+        #vis trait #field_state_trait_ident {
+            /// Whether the field is set.
+            const IS_SET: bool;
         }
+
+        #(#all_markers)*
+    }
+}
+
+/// Aliases of the state of a new builder, and of the state of a builder whose required fields
+/// only have been set.
+fn state_aliases(from_struct: &FromStruct) -> TokenStream {
+    // The type changing parameters are the types of the fields that aren't set yet.
+    let type_changing_params = from_struct
+        .fields
+        .iter()
+        .filter_map(|field| from_struct.generics.type_changing_param(field))
+        .collect::<Vec<_>>();
+    let defaulted_type_changing_params = from_struct
+        .fields
+        .iter()
+        .filter(|field| field.has_default())
+        .filter_map(|field| from_struct.generics.type_changing_param(field))
+        .collect::<Vec<_>>();
+
+    let empty_generics = from_struct
+        .generics
+        .alias_declarations(&type_changing_params);
+    let empty_generics_names = from_struct.generics.initial_names();
+    let ready_generics = from_struct
+        .generics
+        .alias_declarations(&defaulted_type_changing_params);
+    let ready_generics_names = from_struct
+        .generics
+        .names_defaulting(&defaulted_type_changing_params);
+
+    let all_unset = from_struct
+        .fields
+        .iter()
+        .map(|field| from_struct.field_marker(field, false));
+
+    let required_set = from_struct
+        .fields
+        .iter()
+        .map(|field| from_struct.field_marker(field, !field.has_default()));
+
+    let builder_state_ident = from_struct.builder_state_ident();
+    let empty_state_ident = from_struct.empty_state_ident();
+    let ready_state_ident = from_struct.ready_state_ident();
+    let vis = &from_struct.vis;
+
+    quote! {
+        /// The state of a new builder.
+        #vis type #empty_state_ident<#(#empty_generics,)*> = #builder_state_ident<#(#empty_generics_names,)*#(#all_unset,)*>;
+
+        /// The state of a builder whose required fields only have been set.
+        #vis type #ready_state_ident<#(#ready_generics,)*> = #builder_state_ident<#(#ready_generics_names,)*#(#required_set,)*>;
     }
 }
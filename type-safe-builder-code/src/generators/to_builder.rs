@@ -0,0 +1,158 @@
+use crate::parse::{Field, FromStruct};
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+
+pub(crate) fn create(from_struct: &FromStruct) -> TokenStream {
+    let all_set_fields = from_struct.fields.iter().map(|field| {
+        let field_ident = field.ident();
+
+        quote! {
+            #field_ident: ::core::option::Option::Some(value.#field_ident)
+        }
+    });
+
+    let all_set = from_struct
+        .fields
+        .iter()
+        .map(|field| from_struct.built_marker(field));
+
+    let all_override_impls = from_struct
+        .fields
+        .iter()
+        .filter(|field| !field.has_multi())
+        .flat_map(|field| {
+            let preset_marker = from_struct
+                .is_preset(field)
+                .then(|| from_struct.preset_marker(field));
+            [Some(from_struct.field_marker(field, true)), preset_marker]
+                .into_iter()
+                .flatten()
+                .map(|set_marker| override_impl_for(field, &set_marker, from_struct))
+        });
+
+    let all_generics = from_struct.generics.all();
+    let all_generics_names = from_struct.generics.all_names();
+
+    let where_clause = from_struct.generics.where_clause();
+
+    let builder_state_ident = from_struct.builder_state_ident();
+    let markers_field_ident = from_struct.markers_field_ident();
+    let self_type = from_struct.self_type();
+    let all_set_state = quote! {
+        #builder_state_ident<#(#all_generics_names,)*#(#all_set,)*>
+    };
+
+    let to_builder_method = from_struct.is_to_builder().then(|| {
+        let to_builder_doc = format!(
+            "Turns this value back into a builder of `{}` with every field set.",
+            from_struct.ident()
+        );
+        quote! {
+            impl<#(#all_generics,)*> #self_type #where_clause {
+                #[doc = #to_builder_doc]
+                pub fn to_builder(self) -> #all_set_state {
+                    ::core::convert::Into::into(self)
+                }
+            }
+        }
+    });
+
+    quote! {
+        impl<#(#all_generics,)*> ::core::convert::From<#self_type> for #all_set_state #where_clause {
+            fn from(value: #self_type) -> Self {
+                #builder_state_ident {
+                    #(#all_set_fields,)*
+                    #markers_field_ident: ::core::marker::PhantomData,
+                }
+            }
+        }
+
+        #to_builder_method
+
+        #(#all_override_impls)*
+    }
+}
+
+/// The method replacing the value of `field`, available once the field is set, as marked by
+/// `set_marker`, so that fields that can only be set once could still be changed in a builder
+/// made with `to_builder`.
+fn override_impl_for(field: &Field, set_marker: &Ident, from_struct: &FromStruct) -> TokenStream {
+    let field_ident = field.ident();
+    let field_type = field.ty();
+    let override_ident = from_struct.override_ident(field);
+
+    let free_placeholder_fields_types = from_struct
+        .fields
+        .iter()
+        .filter(|other_field| other_field.ident() != field_ident)
+        .map(|other_field| other_field.field_placeholder());
+    let placeholder_fields = from_struct
+        .fields
+        .iter()
+        .map(|other_field| {
+            if other_field.ident() == field_ident {
+                quote! {#set_marker}
+            } else {
+                other_field.field_placeholder()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let copy_other_fields = from_struct
+        .fields
+        .iter()
+        .filter(|other_field| other_field.ident() != field_ident)
+        .map(|other_field| {
+            let other_field_ident = other_field.ident();
+            quote! {#other_field_ident: self.#other_field_ident}
+        });
+
+    let generics = from_struct.generics.state_all();
+    let all_generics_names = from_struct.generics.all_names();
+    let where_clause = from_struct.generics.state_where_clause();
+
+    // Overriding a field whose type is a type changing parameter changes the parameter to the type
+    // of the value, as setting it does.
+    let (value_type, override_generics, override_where_clause, output_generics_names) =
+        match from_struct.generics.type_changing_param(field) {
+            Some(param) => {
+                let new_param = from_struct.unique_param_ident(&format!("New{}", param.unraw()));
+                let bounds = from_struct.generics.bounds_of(param, &new_param);
+                let override_where_clause =
+                    (!bounds.is_empty()).then(|| quote! {where #new_param: #(#bounds)+*});
+                let output_generics_names = all_generics_names
+                    .iter()
+                    .map(|name| {
+                        if *param == name.to_string() {
+                            quote! {#new_param}
+                        } else {
+                            name.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                (
+                    quote! {#new_param},
+                    Some(quote! {<#new_param>}),
+                    override_where_clause,
+                    output_generics_names,
+                )
+            }
+            None => (quote! {#field_type}, None, None, all_generics_names.clone()),
+        };
+
+    let builder_state_ident = from_struct.builder_state_ident();
+    let markers_field_ident = from_struct.markers_field_ident();
+
+    quote! {
+        impl<#(#generics,)*#(#free_placeholder_fields_types,)*> #builder_state_ident<#(#all_generics_names,)*#(#placeholder_fields,)*> #where_clause {
+            fn #override_ident #override_generics(self, value: #value_type) -> #builder_state_ident<#(#output_generics_names,)*#(#placeholder_fields,)*> #override_where_clause {
+                #builder_state_ident {
+                    #field_ident: ::core::option::Option::Some(value),
+                    #(#copy_other_fields,)*
+                    #markers_field_ident: ::core::marker::PhantomData,
+                }
+            }
+        }
+    }
+}
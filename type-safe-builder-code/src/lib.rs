@@ -14,6 +14,15 @@ pub fn builder_for(item: TokenStream) -> TokenStream {
     let builder_state_struct = generators::state_struct::create(&from_struct);
     let all_field_setter_impl = generators::all_field_setters::create(&from_struct);
     let build_impl = generators::build::create(&from_struct);
+    let all_constraint_traits = generators::constraints::create(&from_struct);
+    let to_builder_impl = generators::to_builder::create(&from_struct);
+    let debug_impl = generators::debug::create(&from_struct);
+    let field_info = generators::field_info::create(&from_struct);
+    let dynamic_builder = generators::dynamic::create(&from_struct);
+    let from_env = generators::env::create(&from_struct);
+    let partial = generators::partial::create(&from_struct);
+    let deserialize_impl = generators::serde::create(&from_struct);
+    let alloc_crate = generators::alloc_crate::create(&from_struct);
 
     quote! {
         #builder_struct
@@ -23,5 +32,14 @@ pub fn builder_for(item: TokenStream) -> TokenStream {
         #(#all_field_setter_impl )*
 
         #build_impl
+        #(#all_constraint_traits)*
+        #to_builder_impl
+        #debug_impl
+        #field_info
+        #dynamic_builder
+        #from_env
+        #partial
+        #deserialize_impl
+        #alloc_crate
     }
 }
@@ -1,18 +1,36 @@
-use proc_macro2::{Ident, TokenStream};
+use proc_macro2::{Delimiter, Ident, Span, TokenStream, TokenTree};
 use quote::{ToTokens, format_ident, quote};
+use std::cmp::Ordering;
 use syn::Data::Struct;
 use syn::Fields::Named;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream, Parser};
 use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
 use syn::{
-    Attribute, DataStruct, DeriveInput, FieldsNamed, GenericParam, Meta, Token, Type, WhereClause,
-    parse2,
+    Attribute, DataStruct, DeriveInput, Expr, FieldsNamed, GenericParam, LitStr, Path, Token, Type,
+    Visibility, WhereClause, WherePredicate, parse2,
 };
 
 pub(crate) struct FromStruct {
     pub(crate) ident: Ident,
+    pub(crate) vis: Visibility,
     pub(crate) generics: StructGenerics,
     pub(crate) fields: Vec<Field>,
+    attrs: Vec<Attribute>,
     builder_ident_name: Option<Ident>,
+    generated_idents: Vec<(GeneratedItem, Ident)>,
+}
+
+/// The generated types and traits named after fields, groups or relations.
+#[derive(PartialEq)]
+enum GeneratedItem {
+    Marker(Ident, bool),
+    Preset(Ident),
+    Required(Ident),
+    Single(Ident),
+    Group(Ident),
+    Relation(Ident, RelationKind, Ident),
 }
 
 impl FromStruct {
@@ -31,19 +49,658 @@ impl FromStruct {
             .map(|builder_ident| format_ident!("{}State", builder_ident))
             .unwrap_or_else(|| format_ident!("{}BuilderState", self.ident))
     }
+
+    /// The state of the builder when no field has been set.
+    pub(crate) fn empty_state_ident(&self) -> Ident {
+        format_ident!("{}Empty", self.builder_ident())
+    }
+
+    /// The state of the builder when only the required fields have been set.
+    pub(crate) fn ready_state_ident(&self) -> Ident {
+        format_ident!("{}Ready", self.builder_ident())
+    }
+
+    /// The trait implemented by the marker types telling whether a field is set.
+    pub(crate) fn field_state_trait_ident(&self) -> Ident {
+        format_ident!("{}FieldState", self.builder_ident())
+    }
+
+    /// The trait giving the value of a field whose type is a parameter with a default type.
+    pub(crate) fn value_or_default_trait_ident(&self) -> Ident {
+        format_ident!("{}ValueOrDefault", self.builder_ident())
+    }
+
+    /// The trait giving the value of a field whose type is a parameter when building over a base.
+    pub(crate) fn value_or_base_trait_ident(&self) -> Ident {
+        format_ident!("{}ValueOrBase", self.builder_ident())
+    }
+
+    /// The marker type of the builder states where `field` is set, or isn't.
+    pub(crate) fn field_marker(&self, field: &Field, is_set: bool) -> Ident {
+        self.generated_ident(GeneratedItem::Marker(field.ident.clone(), is_set))
+    }
+
+    /// Whether `field` excludes other fields, being a member of an exclusive group or conflicting
+    /// with another field, so that it can't be marked as set in a builder made out of a built
+    /// value, where all the fields are set.
+    pub(crate) fn is_preset(&self, field: &Field) -> bool {
+        self.groups()
+            .iter()
+            .any(|group| group.rule.is_exclusive() && group.contains(field))
+            || self.relations().iter().any(|relation| {
+                relation.kind == RelationKind::ConflictsWith
+                    && (relation.field.ident == field.ident || relation.other.ident == field.ident)
+            })
+    }
+
+    /// The marker type of the builder states made out of a built value, where the preset `field`
+    /// is set to the value it has been built with.
+    pub(crate) fn preset_marker(&self, field: &Field) -> Ident {
+        self.generated_ident(GeneratedItem::Preset(field.ident.clone()))
+    }
+
+    /// The marker type of the builder states made out of a built value, where `field` is set.
+    pub(crate) fn built_marker(&self, field: &Field) -> Ident {
+        if self.is_preset(field) {
+            self.preset_marker(field)
+        } else {
+            self.field_marker(field, true)
+        }
+    }
+
+    /// The trait implemented by the builder states where the required `field` is set.
+    pub(crate) fn required_trait_ident(&self, field: &Field) -> Ident {
+        self.generated_ident(GeneratedItem::Required(field.ident.clone()))
+    }
+
+    /// The trait implemented by the builder states where `field` can still be set.
+    pub(crate) fn single_trait_ident(&self, field: &Field) -> Ident {
+        self.generated_ident(GeneratedItem::Single(field.ident.clone()))
+    }
+
+    /// The trait implemented by the builder states satisfying the rule of `group`.
+    pub(crate) fn group_trait_ident(&self, group: &Group) -> Ident {
+        self.generated_ident(GeneratedItem::Group(group.name.clone()))
+    }
+
+    /// The trait implemented by the builder states where `relation` holds.
+    pub(crate) fn relation_trait_ident(&self, relation: &Relation) -> Ident {
+        self.generated_ident(GeneratedItem::Relation(
+            relation.field.ident.clone(),
+            relation.kind,
+            relation.other.ident.clone(),
+        ))
+    }
+
+    fn generated_ident(&self, item: GeneratedItem) -> Ident {
+        self.generated_idents
+            .iter()
+            .find(|(generated_item, _)| *generated_item == item)
+            .map(|(_, ident)| ident.clone())
+            .unwrap()
+    }
+
+    /// Names the markers and the state traits after the fields, groups and relations, so that
+    /// they differ from each other and from the other generated items even when the names they
+    /// are made of run together, as `foo` and `_foo`, or `foo` set and `foo_is` set do.
+    fn name_generated_items(&self) -> Vec<(GeneratedItem, Ident)> {
+        let builder_ident = self.builder_ident();
+        let mut taken_idents = vec![
+            self.ident.clone(),
+            builder_ident.clone(),
+            self.builder_state_ident(),
+            self.empty_state_ident(),
+            self.ready_state_ident(),
+            self.field_state_trait_ident(),
+            self.value_or_default_trait_ident(),
+            self.value_or_base_trait_ident(),
+            self.field_info_ident(),
+            self.dyn_builder_ident(),
+            self.dyn_builder_error_ident(),
+            self.partial_ident(),
+            self.env_error_ident(),
+            self.alloc_crate_ident(),
+        ];
+
+        let markers = self.fields.iter().flat_map(|field| {
+            [(true, "Set"), (false, "Unset")].map(|(is_set, state)| {
+                (
+                    GeneratedItem::Marker(field.ident.clone(), is_set),
+                    format!("{}{}{}", builder_ident, camel_case(&field.ident), state),
+                )
+            })
+        });
+        let preset_markers = self
+            .fields
+            .iter()
+            .filter(|field| self.is_preset(field))
+            .map(|field| {
+                (
+                    GeneratedItem::Preset(field.ident.clone()),
+                    format!("{}{}Preset", builder_ident, camel_case(&field.ident)),
+                )
+            });
+        let required_traits =
+            self.fields
+                .iter()
+                .filter(|field| !field.has_default())
+                .map(|field| {
+                    (
+                        GeneratedItem::Required(field.ident.clone()),
+                        format!("{}{}IsSet", builder_ident, camel_case(&field.ident)),
+                    )
+                });
+        let single_traits = self
+            .fields
+            .iter()
+            .filter(|field| !field.has_multi())
+            .map(|field| {
+                (
+                    GeneratedItem::Single(field.ident.clone()),
+                    format!("{}{}IsUnset", builder_ident, camel_case(&field.ident)),
+                )
+            });
+        let group_traits = self.groups().into_iter().map(|group| {
+            (
+                GeneratedItem::Group(group.name.clone()),
+                format!("{}{}Group", builder_ident, camel_case(&group.name)),
+            )
+        });
+        let relation_traits = self.relations().into_iter().map(|relation| {
+            let kind = match relation.kind {
+                RelationKind::Requires => "Requires",
+                RelationKind::ConflictsWith => "ConflictsWith",
+            };
+            (
+                GeneratedItem::Relation(
+                    relation.field.ident.clone(),
+                    relation.kind,
+                    relation.other.ident.clone(),
+                ),
+                format!(
+                    "{}{}{}{}",
+                    builder_ident,
+                    camel_case(&relation.field.ident),
+                    kind,
+                    camel_case(&relation.other.ident)
+                ),
+            )
+        });
+
+        markers
+            .chain(preset_markers)
+            .chain(required_traits)
+            .chain(single_traits)
+            .chain(group_traits)
+            .chain(relation_traits)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|(item, name)| {
+                let ident = unique_type_ident(&name, &taken_idents, builder_ident.span());
+                taken_idents.push(ident.clone());
+                (item, ident)
+            })
+            .collect()
+    }
+
+    /// The field of the builder state holding the marker types.
+    pub(crate) fn markers_field_ident(&self) -> Ident {
+        let field_idents = self
+            .fields
+            .iter()
+            .map(|field| field.ident.clone())
+            .collect::<Vec<_>>();
+        unique_ident("markers", &field_idents)
+    }
+
+    pub(crate) fn field_info_ident(&self) -> Ident {
+        format_ident!("{}FieldInfo", self.builder_ident())
+    }
+
+    pub(crate) fn dyn_builder_ident(&self) -> Ident {
+        self.builder_ident_name
+            .clone()
+            .map(|builder_ident| format_ident!("{}Dyn", builder_ident))
+            .unwrap_or_else(|| format_ident!("{}DynBuilder", self.ident))
+    }
+
+    pub(crate) fn dyn_builder_error_ident(&self) -> Ident {
+        format_ident!("{}Error", self.dyn_builder_ident())
+    }
+
+    pub(crate) fn partial_ident(&self) -> Ident {
+        format_ident!("{}Partial", self.ident)
+    }
+
+    /// The name the `alloc` crate is imported under.
+    pub(crate) fn alloc_crate_ident(&self) -> Ident {
+        format_ident!("{}Alloc", self.builder_ident())
+    }
+
+    pub(crate) fn is_dynamic(&self) -> bool {
+        has_attr_path(&self.attrs, "dynamic")
+            || self.is_from_pairs()
+            || self.is_from_env()
+            || self.is_partial()
+    }
+
+    pub(crate) fn is_to_builder(&self) -> bool {
+        has_attr_path(&self.attrs, "to_builder")
+    }
+
+    pub(crate) fn is_staged(&self) -> bool {
+        has_attr_path(&self.attrs, "staged")
+    }
+
+    pub(crate) fn is_partial(&self) -> bool {
+        has_attr_path(&self.attrs, "partial")
+    }
+
+    pub(crate) fn partial_attrs(&self) -> Vec<TokenStream> {
+        get_attr_lists(&self.attrs, "partial_attr")
+    }
+
+    pub(crate) fn env_error_ident(&self) -> Ident {
+        format_ident!("{}EnvError", self.builder_ident())
+    }
+
+    pub(crate) fn is_from_env(&self) -> bool {
+        self.env_prefix().is_some() || self.fields.iter().any(|field| field.env().is_some())
+    }
+
+    fn env_prefix(&self) -> Option<String> {
+        get_attr_value(&self.attrs, "env_prefix").map(lit_str_value)
+    }
+
+    /// The environment variable a field is loaded from, if any.
+    pub(crate) fn env_var(&self, field: &Field) -> Option<String> {
+        if field.is_skip_env() {
+            return None;
+        }
+        field.env().or_else(|| {
+            self.env_prefix()
+                .map(|prefix| format!("{}{}", prefix, field.name().to_uppercase()))
+        })
+    }
+
+    pub(crate) fn is_serde(&self) -> bool {
+        has_attr_path(&self.attrs, "serde")
+    }
+
+    pub(crate) fn serde_attrs(&self) -> Vec<&Attribute> {
+        serde_attrs(&self.attrs)
+    }
+
+    pub(crate) fn is_from_pairs(&self) -> bool {
+        has_attr_path(&self.attrs, "from_pairs")
+    }
+
+    pub(crate) fn self_type(&self) -> TokenStream {
+        let ident = self.ident();
+        let all_generics_names = self.generics.all_names();
+        quote! {
+            #ident<#(#all_generics_names,)*>
+        }
+    }
+
+    /// The struct type as written in expressions, to replace `Self` in them.
+    pub(crate) fn self_expr(&self) -> TokenStream {
+        let ident = self.ident();
+        let all_generics_names = self.generics.all_names();
+        quote! {
+            #ident::<#(#all_generics_names,)*>
+        }
+    }
+
+    /// The predicates replacing the inferred ones needed by the default values of the fields.
+    pub(crate) fn bound(&self) -> Option<Vec<TokenStream>> {
+        get_attr_value(&self.attrs, "bound").map(parse_predicates)
+    }
+
+    /// The method replacing the value of `field` once it is set, named after its setter.
+    pub(crate) fn override_ident(&self, field: &Field) -> Ident {
+        self.unique_method_ident(&format!("override_{}", field.setter_name()))
+    }
+
+    /// The method building the struct over a base value.
+    pub(crate) fn build_over_ident(&self) -> Ident {
+        self.unique_method_ident("build_over")
+    }
+
+    /// The method telling whether a field is set, given its name.
+    pub(crate) fn is_set_ident(&self) -> Ident {
+        self.unique_method_ident("is_set")
+    }
+
+    /// A method of the builder states named after `name`, made different from the setters.
+    fn unique_method_ident(&self, name: &str) -> Ident {
+        let setter_idents = self
+            .fields
+            .iter()
+            .map(|field| format_ident!("{}", field.setter_name()))
+            .collect::<Vec<_>>();
+        unique_ident(name, &setter_idents)
+    }
+
+    /// An identifier named after `name` that is neither a struct parameter nor a placeholder.
+    pub(crate) fn unique_param_ident(&self, name: &str) -> Ident {
+        let taken_idents = self
+            .generics
+            .generics
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Lifetime(_) => None,
+                GenericParam::Type(t) => Some(t.ident.clone()),
+                GenericParam::Const(c) => Some(c.ident.clone()),
+            })
+            .chain(self.fields.iter().map(|field| field.placeholder.clone()))
+            .collect::<Vec<_>>();
+        unique_ident(name, &taken_idents)
+    }
+
+    pub(crate) fn build_into(&self) -> Option<TokenStream> {
+        get_attr_value(&self.attrs, "build_into").map(|ty| replace_self(ty, &self.self_type()))
+    }
+
+    /// The type returned by `build()`.
+    pub(crate) fn built_type(&self) -> TokenStream {
+        self.build_into().unwrap_or_else(|| self.self_type())
+    }
+
+    pub(crate) fn post_build(&self) -> Option<TokenStream> {
+        get_attr_value(&self.attrs, "post_build").map(|post| replace_self(post, &self.self_expr()))
+    }
+
+    pub(crate) fn state_attrs(&self) -> Vec<TokenStream> {
+        let derives = self
+            .state_derives()
+            .into_iter()
+            .filter(|derive| !derive.is_ident("Debug"))
+            .collect::<Vec<_>>();
+        let derive_attr = (!derives.is_empty()).then(|| quote! {derive(#(#derives),*)});
+        derive_attr
+            .into_iter()
+            .chain(get_attr_lists(&self.attrs, "state_attr"))
+            .collect()
+    }
+
+    /// Debug is not derived but generated, so the state of each field is shown.
+    pub(crate) fn has_debug(&self) -> bool {
+        self.state_derives()
+            .iter()
+            .any(|derive| derive.is_ident("Debug"))
+    }
+
+    fn state_derives(&self) -> Vec<Path> {
+        get_attr_lists(&self.attrs, "derive")
+            .into_iter()
+            .flat_map(|derives| {
+                Punctuated::<Path, Token![,]>::parse_terminated
+                    .parse2(derives)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    pub(crate) fn factory_attrs(&self) -> Vec<TokenStream> {
+        get_attr_lists(&self.attrs, "factory_attr")
+    }
+
+    /// The field groups, in the order their first member is declared.
+    pub(crate) fn groups(&self) -> Vec<Group<'_>> {
+        let mut groups: Vec<Group> = vec![];
+        for field in &self.fields {
+            let Some(name) = field.group() else {
+                continue;
+            };
+            if !field.has_mandatory() && field.explicit_default().is_none() && !field.is_option() {
+                panic!(
+                    "member `{}` of group `{name}` must be an `Option` or have a default value",
+                    field.ident()
+                );
+            }
+            let rule = field.group_rule();
+            match groups.iter_mut().find(|group| group.name == name) {
+                Some(group) => {
+                    if rule.is_some_and(|rule| rule != group.rule) {
+                        panic!("group `{name}` has conflicting rules");
+                    }
+                    group.members.push(field);
+                }
+                None => groups.push(Group {
+                    rule: rule.or_else(|| {
+                        self.fields
+                            .iter()
+                            .filter(|other| other.group().as_ref() == Some(&name))
+                            .find_map(Field::group_rule)
+                    }).unwrap_or_else(|| {
+                        panic!("group `{name}` needs one of `exactly_one`, `at_least_one` or `at_most_one`")
+                    }),
+                    name,
+                    members: vec![field],
+                }),
+            }
+        }
+        groups
+    }
+
+    /// The `requires` and `conflicts_with` relations between fields, in declaration order.
+    pub(crate) fn relations(&self) -> Vec<Relation<'_>> {
+        self.fields
+            .iter()
+            .flat_map(|field| {
+                [RelationKind::Requires, RelationKind::ConflictsWith]
+                    .into_iter()
+                    .flat_map(move |kind| {
+                        get_attr_values(&field.attrs, kind.attr_name())
+                            .into_iter()
+                            .map(move |other| (kind, parse2::<Ident>(other).unwrap()))
+                    })
+                    .inspect(move |(kind, other)| {
+                        if other == field.ident() {
+                            panic!("field `{other}` {} itself", kind.description());
+                        }
+                    })
+                    .map(move |(kind, other)| Relation {
+                        field,
+                        kind,
+                        other: self
+                            .fields
+                            .iter()
+                            .find(|candidate| *candidate.ident() == other)
+                            .unwrap_or_else(|| {
+                                panic!(
+                                    "field `{}` {} unknown field `{other}`",
+                                    field.ident(),
+                                    kind.description()
+                                )
+                            }),
+                    })
+            })
+            .collect()
+    }
+
+    /// The local variable holding the value of `field` while the struct is built. Only the fields
+    /// some default value refers to are bound to their name as the default values see it, so that
+    /// the other fields don't shadow the functions or constants these values refer to.
+    pub(crate) fn field_local(&self, field: &Field) -> Ident {
+        let mut local = field.ident().clone();
+        let is_dependency = self.fields.iter().any(|other| {
+            other
+                .default_dependencies(&self.fields)
+                .contains(&field.ident())
+        });
+        if !is_dependency {
+            local.set_span(Span::mixed_site());
+        }
+        local
+    }
+
+    /// Fields ordered so that every field comes after the fields its default value refers to.
+    pub(crate) fn fields_in_default_order(&self) -> Vec<&Field> {
+        let mut ordered: Vec<&Field> = Vec::with_capacity(self.fields.len());
+        let mut pending: Vec<&Field> = self.fields.iter().collect();
+
+        while !pending.is_empty() {
+            let (ready, not_ready): (Vec<&Field>, Vec<&Field>) =
+                pending.into_iter().partition(|field| {
+                    field
+                        .default_dependencies(&self.fields)
+                        .iter()
+                        .all(|dependency| {
+                            ordered
+                                .iter()
+                                .any(|resolved| resolved.ident() == *dependency)
+                        })
+                });
+            if ready.is_empty() {
+                let cycle = not_ready
+                    .iter()
+                    .map(|field| format!("`{}`", field.ident()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                panic!("default values of fields {cycle} depend on each other");
+            }
+            ordered.extend(ready);
+            pending = not_ready;
+        }
+
+        ordered
+    }
 }
 
 pub(crate) struct Field {
     ident: Ident,
+    /// The const parameter of the builder state telling whether the field is set.
+    placeholder: Ident,
+    /// The type with `Self` replaced by the struct type, as `Self` means the builder state in the
+    /// generated code.
     ty: Type,
+    declared_ty: Type,
     attrs: Vec<Attribute>,
     is_default_as_standard: bool,
+    is_default_from_struct: bool,
     is_default_as_multi: bool,
 }
 
+pub(crate) struct Group<'a> {
+    pub(crate) name: Ident,
+    pub(crate) rule: GroupRule,
+    pub(crate) members: Vec<&'a Field>,
+}
+
+impl Group<'_> {
+    pub(crate) fn contains(&self, field: &Field) -> bool {
+        self.members
+            .iter()
+            .any(|member| member.ident() == field.ident())
+    }
+}
+
+/// How many members of a group must be set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum GroupRule {
+    Exactly,
+    AtLeast,
+    AtMost,
+}
+
+impl GroupRule {
+    fn attr_name(self) -> &'static str {
+        match self {
+            GroupRule::Exactly => "exactly_one",
+            GroupRule::AtLeast => "at_least_one",
+            GroupRule::AtMost => "at_most_one",
+        }
+    }
+
+    /// How the rule reads in messages, e.g. "exactly one".
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            GroupRule::Exactly => "exactly one",
+            GroupRule::AtLeast => "at least one",
+            GroupRule::AtMost => "at most one",
+        }
+    }
+
+    /// The states of the members satisfying the rule, telling for each member whether it is set,
+    /// or `None` when it doesn't matter. There is one for each member being the first one set,
+    /// and one for no member being set if allowed, so that there are as many as members.
+    pub(crate) fn allowed_states(self, members: usize) -> Vec<Vec<Option<bool>>> {
+        let first_set = (0..members).map(|first| {
+            (0..members)
+                .map(|index| match index.cmp(&first) {
+                    Ordering::Less => Some(false),
+                    Ordering::Equal => Some(true),
+                    Ordering::Greater if self == GroupRule::AtLeast => None,
+                    Ordering::Greater => Some(false),
+                })
+                .collect()
+        });
+        let none_set = (self == GroupRule::AtMost).then(|| vec![Some(false); members]);
+        first_set.chain(none_set).collect()
+    }
+
+    /// Exclusive groups don't allow setting a member once another one has been set.
+    pub(crate) fn is_exclusive(self) -> bool {
+        self != GroupRule::AtLeast
+    }
+}
+
+pub(crate) struct Relation<'a> {
+    pub(crate) field: &'a Field,
+    pub(crate) kind: RelationKind,
+    pub(crate) other: &'a Field,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum RelationKind {
+    Requires,
+    ConflictsWith,
+}
+
+impl RelationKind {
+    fn attr_name(self) -> &'static str {
+        match self {
+            RelationKind::Requires => "requires",
+            RelationKind::ConflictsWith => "conflicts_with",
+        }
+    }
+
+    /// How the relation reads in messages, e.g. "requires".
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            RelationKind::Requires => "requires",
+            RelationKind::ConflictsWith => "conflicts with",
+        }
+    }
+
+    /// Whether the relation holds given whether the field and the other field are set.
+    pub(crate) fn allows(self, field_set: bool, other_set: bool) -> bool {
+        match self {
+            RelationKind::Requires => !field_set || other_set,
+            RelationKind::ConflictsWith => !(field_set && other_set),
+        }
+    }
+}
+
 pub(crate) enum DefaultToSet {
     AsDefault,
     AsValue(TokenStream),
+    WithFn(TokenStream),
+    FromStructDefault,
+}
+
+impl DefaultToSet {
+    /// The default value as written in the builder attribute, if there is one.
+    pub(crate) fn source_text(&self) -> Option<String> {
+        match self {
+            DefaultToSet::AsDefault => None,
+            DefaultToSet::AsValue(value) => Some(source_text(value)),
+            DefaultToSet::WithFn(default_fn) => Some(format!("{}()", source_text(default_fn))),
+            DefaultToSet::FromStructDefault => None,
+        }
+    }
 }
 impl Field {
     pub(crate) fn ident(&self) -> &Ident {
@@ -54,17 +711,41 @@ impl Field {
         self.get_attr_value("setter_name")
     }
 
+    /// The name of the setter, without the `r#` prefix of raw identifiers.
+    pub(crate) fn setter_name(&self) -> String {
+        self.setter_ident()
+            .map(|setter_ident| parse2::<Ident>(setter_ident).unwrap().unraw().to_string())
+            .unwrap_or_else(|| self.name())
+    }
+
     pub(crate) fn ty(&self) -> &Type {
         &self.ty
     }
 
+    pub(crate) fn type_name(&self) -> String {
+        source_text(&self.declared_ty.to_token_stream())
+    }
+
+    /// The name of the field, without the `r#` prefix of raw identifiers.
+    pub(crate) fn name(&self) -> String {
+        self.ident.unraw().to_string()
+    }
+
+    /// The parameter of the builder state that is the marker type telling whether the field is set.
     pub(crate) fn field_placeholder(&self) -> TokenStream {
-        format_ident!("PLACEHOLDER{}TYPE", self.ident.to_string().to_uppercase()).to_token_stream()
+        self.placeholder.to_token_stream()
     }
 
-    pub(crate) fn const_field_placeholder(&self) -> TokenStream {
-        let field_placeholder = self.field_placeholder();
-        quote! {const #field_placeholder: bool}
+    fn default_dependencies<'a>(&self, fields: &'a [Field]) -> Vec<&'a Ident> {
+        let Some(DefaultToSet::AsValue(value)) = self.default_to_set() else {
+            return vec![];
+        };
+        let mentioned = referred_variables(value);
+        fields
+            .iter()
+            .map(Field::ident)
+            .filter(|ident| *ident != self.ident() && mentioned.contains(ident))
+            .collect()
     }
 
     pub(crate) fn has_default(&self) -> bool {
@@ -74,21 +755,83 @@ impl Field {
         if self.has_mandatory() {
             return None;
         }
+        self.explicit_default().or_else(|| {
+            // Group members that don't have a default value are `Option`s.
+            self.group().is_some().then_some(DefaultToSet::AsDefault)
+        })
+    }
+
+    /// The default value given to the field by the attributes of the field or of the struct.
+    fn explicit_default(&self) -> Option<DefaultToSet> {
         self.get_attr_value("default")
             .map(DefaultToSet::AsValue)
+            .or_else(|| {
+                self.get_attr_value("default_with")
+                    .map(DefaultToSet::WithFn)
+            })
             .or_else(|| {
                 if self.has_attr_path("default") || self.is_default_as_standard {
                     Some(DefaultToSet::AsDefault)
+                } else if self.is_default_from_struct {
+                    Some(DefaultToSet::FromStructDefault)
                 } else {
                     None
                 }
             })
     }
 
+    fn is_option(&self) -> bool {
+        matches!(
+            &self.declared_ty,
+            Type::Path(path) if path.qself.is_none()
+                && path.path.segments.last().is_some_and(|segment| segment.ident == "Option")
+        )
+    }
+
+    /// The predicates replacing the inferred ones needed by the default value of the field.
+    pub(crate) fn bound(&self) -> Option<Vec<TokenStream>> {
+        self.get_attr_value("bound").map(parse_predicates)
+    }
+
+    fn group(&self) -> Option<Ident> {
+        self.get_attr_value("group")
+            .map(|group| parse2::<Ident>(group).unwrap())
+    }
+
+    fn group_rule(&self) -> Option<GroupRule> {
+        [GroupRule::Exactly, GroupRule::AtLeast, GroupRule::AtMost]
+            .into_iter()
+            .find(|rule| self.has_attr_path(rule.attr_name()))
+    }
+
     fn has_mandatory(&self) -> bool {
         self.has_attr_path("mandatory")
     }
 
+    fn env(&self) -> Option<String> {
+        self.get_attr_value("env").map(lit_str_value)
+    }
+
+    /// Whether the field can't be set by name, as its type can't be parsed from a string.
+    pub(crate) fn is_skip_pairs(&self) -> bool {
+        self.has_attr_path("skip_pairs")
+    }
+
+    /// Whether the field isn't loaded from the environment, as its type can't be parsed from a
+    /// string.
+    fn is_skip_env(&self) -> bool {
+        self.has_attr_path("skip_env")
+    }
+
+    pub(crate) fn is_debug_redacted(&self) -> bool {
+        self.get_attr_value("debug")
+            .is_some_and(|value| value.to_string() == "redact")
+    }
+
+    pub(crate) fn serde_attrs(&self) -> Vec<&Attribute> {
+        serde_attrs(&self.attrs)
+    }
+
     pub(crate) fn has_multi(&self) -> bool {
         (self.is_default_as_multi && !self.has_single()) || self.has_attr_path("multi")
     }
@@ -109,6 +852,9 @@ impl Field {
 pub(crate) struct StructGenerics {
     where_clause: Option<WhereClause>,
     generics: Vec<GenericParam>,
+    /// Type parameters whose type changes when their field is set. Builder states don't bound them,
+    /// so that states whose field isn't set yet can hold a placeholder type.
+    type_changing: Vec<Ident>,
 }
 
 impl StructGenerics {
@@ -120,13 +866,240 @@ impl StructGenerics {
         })
     }
 
-    pub(crate) fn all(&self) -> Vec<TokenStream> {
+    pub(crate) fn where_clause_with(&self, predicates: Vec<TokenStream>) -> Option<TokenStream> {
+        if predicates.is_empty() {
+            return self.where_clause();
+        }
+        let existing_predicates = self
+            .where_clause
+            .iter()
+            .flat_map(|clause| clause.predicates.iter());
+        Some(quote! {
+            where #(#existing_predicates,)* #(#predicates,)*
+        })
+    }
+
+    /// The type changing parameter that is the type of `field`, if any.
+    pub(crate) fn type_changing_param(&self, field: &Field) -> Option<&Ident> {
+        self.type_changing
+            .iter()
+            .find(|param| is_type_param(field.ty(), param))
+    }
+
+    fn is_type_changing(&self, ident: &Ident) -> bool {
+        self.type_changing.contains(ident)
+    }
+
+    pub(crate) fn has_defaults(&self) -> bool {
+        self.generics.iter().any(|param| match param {
+            GenericParam::Lifetime(_) => false,
+            GenericParam::Type(t) => t.default.is_some(),
+            GenericParam::Const(c) => c.default.is_some(),
+        })
+    }
+
+    /// The parameters as declared, with their default values, to declare generated structs.
+    pub(crate) fn declarations(&self) -> Vec<TokenStream> {
+        self.generics.iter().map(|param| quote! {#param}).collect()
+    }
+
+    /// Like [`Self::declarations`], without the given parameters, and without bounds, which type
+    /// aliases don't enforce.
+    pub(crate) fn alias_declarations(&self, without: &[&Ident]) -> Vec<TokenStream> {
+        self.generics
+            .iter()
+            .filter(|param| !matches!(param, GenericParam::Type(t) if without.contains(&&t.ident)))
+            .map(|param| match param {
+                GenericParam::Lifetime(l) => {
+                    let lifetime = &l.lifetime;
+                    quote! {#lifetime}
+                }
+                GenericParam::Type(t) => {
+                    let i = &t.ident;
+                    let default = t.default.iter();
+                    quote! {#i #(= #default)*}
+                }
+                param => quote! {#param},
+            })
+            .collect()
+    }
+
+    /// Like [`Self::declarations`], without the bounds of the type changing parameters.
+    pub(crate) fn state_declarations(&self) -> Vec<TokenStream> {
+        self.generics
+            .iter()
+            .map(|param| match param {
+                GenericParam::Type(t) if self.is_type_changing(&t.ident) => {
+                    let i = &t.ident;
+                    let default = t.default.iter();
+                    quote! {#i #(= #default)*}
+                }
+                param => quote! {#param},
+            })
+            .collect()
+    }
+
+    /// Like [`Self::all`], without the bounds of the type changing parameters.
+    pub(crate) fn state_all(&self) -> Vec<TokenStream> {
+        self.generics
+            .iter()
+            .map(|param| match param {
+                GenericParam::Type(t) if self.is_type_changing(&t.ident) => {
+                    let i = &t.ident;
+                    quote! {#i}
+                }
+                param => without_default(param),
+            })
+            .collect()
+    }
+
+    /// Like [`Self::all`], without the type changing parameters.
+    pub(crate) fn fixed_all(&self) -> Vec<TokenStream> {
+        self.all_without(&self.type_changing.iter().collect::<Vec<_>>())
+    }
+
+    /// Like [`Self::all`], without the given parameters.
+    pub(crate) fn all_without(&self, params: &[&Ident]) -> Vec<TokenStream> {
+        self.generics
+            .iter()
+            .filter(|param| !matches!(param, GenericParam::Type(t) if params.contains(&&t.ident)))
+            .map(without_default)
+            .collect()
+    }
+
+    /// Like [`Self::all_names`], with the type the type changing parameters have before their
+    /// field is set: their default type, or `()` if they have none.
+    pub(crate) fn initial_names(&self) -> Vec<TokenStream> {
+        self.generics
+            .iter()
+            .zip(self.all_names())
+            .map(|(param, name)| match param {
+                GenericParam::Type(t) if self.is_type_changing(&t.ident) => match &t.default {
+                    Some(default) => quote! {#default},
+                    None => quote! {()},
+                },
+                _ => name,
+            })
+            .collect()
+    }
+
+    /// Like [`Self::all_names`], with the given parameters replaced by their default type.
+    pub(crate) fn names_defaulting(&self, params: &[&Ident]) -> Vec<TokenStream> {
+        self.generics
+            .iter()
+            .zip(self.all_names())
+            .map(|(param, name)| match param {
+                GenericParam::Type(t) if params.contains(&&t.ident) => {
+                    let default = &t.default;
+                    quote! {#default}
+                }
+                _ => name,
+            })
+            .collect()
+    }
+
+    /// Replaces the given parameters by their default type in `tokens`.
+    pub(crate) fn replace_with_defaults(
+        &self,
+        tokens: TokenStream,
+        params: &[&Ident],
+    ) -> TokenStream {
         self.generics
             .iter()
-            .map(|param| {
-                quote! {
-                    #param
+            .fold(tokens, |tokens, param| match param {
+                GenericParam::Type(t) if params.contains(&&t.ident) => {
+                    let default = &t.default;
+                    replace_ident(tokens, &t.ident.to_string(), &quote! {#default})
                 }
+                _ => tokens,
+            })
+    }
+
+    /// Like [`Self::where_clause`], without the predicates bounding the type changing parameters.
+    pub(crate) fn state_where_clause(&self) -> Option<TokenStream> {
+        self.state_where_clause_with(vec![])
+    }
+
+    pub(crate) fn state_where_clause_with(
+        &self,
+        predicates: Vec<TokenStream>,
+    ) -> Option<TokenStream> {
+        self.where_clause_without(&self.type_changing.iter().collect::<Vec<_>>(), predicates)
+    }
+
+    /// Like [`Self::where_clause_with`], without the predicates bounding the given parameters.
+    pub(crate) fn where_clause_without(
+        &self,
+        params: &[&Ident],
+        predicates: Vec<TokenStream>,
+    ) -> Option<TokenStream> {
+        let existing_predicates = self
+            .where_clause
+            .iter()
+            .flat_map(|clause| clause.predicates.iter())
+            .filter(|predicate| {
+                !bounded_param(predicate).is_some_and(|param| params.contains(&param))
+            })
+            .map(|predicate| quote! {#predicate})
+            .collect::<Vec<_>>();
+        if existing_predicates.is_empty() && predicates.is_empty() {
+            return None;
+        }
+        Some(quote! {
+            where #(#existing_predicates,)* #(#predicates,)*
+        })
+    }
+
+    /// The bounds of a type changing parameter, with the parameter replaced by `replacement`.
+    pub(crate) fn bounds_of(&self, param: &Ident, replacement: &Ident) -> Vec<TokenStream> {
+        let inline_bounds = self
+            .generics
+            .iter()
+            .flat_map(|generic_param| match generic_param {
+                GenericParam::Type(t) if t.ident == *param => {
+                    t.bounds.iter().map(|bound| quote! {#bound}).collect()
+                }
+                _ => vec![],
+            });
+        let where_bounds = self
+            .where_clause
+            .iter()
+            .flat_map(|clause| clause.predicates.iter())
+            .filter_map(|predicate| match predicate {
+                WherePredicate::Type(predicate) if is_type_param(&predicate.bounded_ty, param) => {
+                    Some(predicate.bounds.iter().map(|bound| quote! {#bound}))
+                }
+                _ => None,
+            })
+            .flatten();
+        let replacement = quote! {#replacement};
+        inline_bounds
+            .chain(where_bounds)
+            .map(|bound| replace_ident(bound, &param.to_string(), &replacement))
+            .collect()
+    }
+
+    /// The parameters, without their default values, as they are written in `impl` and `fn`.
+    pub(crate) fn all(&self) -> Vec<TokenStream> {
+        self.generics.iter().map(without_default).collect()
+    }
+
+    /// Whether `ty` mentions a type or const parameter.
+    pub(crate) fn is_generic(&self, ty: &Type) -> bool {
+        let mentioned = mentioned_idents(ty.to_token_stream());
+        self.generics.iter().any(|param| match param {
+            GenericParam::Lifetime(_) => false,
+            GenericParam::Type(t) => mentioned.contains(&t.ident),
+            GenericParam::Const(c) => mentioned.contains(&c.ident),
+        })
+    }
+
+    pub(crate) fn type_names(&self) -> Vec<&Ident> {
+        self.generics
+            .iter()
+            .filter_map(|generic_param| match generic_param {
+                GenericParam::Type(t) => Some(&t.ident),
+                _ => None,
             })
             .collect()
     }
@@ -156,18 +1129,47 @@ pub(super) fn parse(item: TokenStream) -> FromStruct {
     let ast: DeriveInput = parse2(item).unwrap();
 
     let is_default_as_standard = has_attr_path(&ast.attrs, "default");
+    let is_default_from_struct =
+        get_attr_value(&ast.attrs, "default").is_some_and(|value| value.to_string() == "struct");
     let is_default_as_multi = has_attr_path(&ast.attrs, "multi");
 
+    let (_, type_generics, _) = ast.generics.split_for_impl();
+    let struct_ident = &ast.ident;
+    let self_type = quote! {#struct_ident #type_generics};
+
+    // The placeholders must differ from each other and from the struct parameters they are
+    // declared with.
+    let mut taken_idents = ast
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .chain(ast.generics.const_params().map(|param| param.ident.clone()))
+        .collect::<Vec<_>>();
+
     let fields = match ast.data {
         Struct(DataStruct {
             fields: Named(FieldsNamed { ref named, .. }),
             ..
-        }) => named.iter().map(|field| Field {
-            ident: field.ident.clone().unwrap(),
-            ty: field.ty.clone(),
-            attrs: field.attrs.clone(),
-            is_default_as_standard,
-            is_default_as_multi,
+        }) => named.iter().map(|field| {
+            let ident = field.ident.clone().unwrap();
+            let placeholder = unique_ident(
+                &format!(
+                    "PLACEHOLDER{}TYPE",
+                    ident.unraw().to_string().to_uppercase()
+                ),
+                &taken_idents,
+            );
+            taken_idents.push(placeholder.clone());
+            Field {
+                ident,
+                placeholder,
+                ty: parse2(replace_self(field.ty.to_token_stream(), &self_type)).unwrap(),
+                declared_ty: field.ty.clone(),
+                attrs: field.attrs.clone(),
+                is_default_as_standard,
+                is_default_from_struct,
+                is_default_as_multi,
+            }
         }),
         _ => unimplemented!("Only implemented for structs"),
     }
@@ -176,59 +1178,486 @@ pub(super) fn parse(item: TokenStream) -> FromStruct {
     let builder_ident_name =
         get_attr_value(&ast.attrs, "name").map(|ts| format_ident!("{}", ts.to_string()));
 
-    FromStruct {
+    let mut from_struct = FromStruct {
         ident: ast.ident,
+        vis: ast.vis,
         generics: StructGenerics {
+            type_changing: type_changing_params(&ast.generics, &fields),
             generics: ast.generics.params.into_iter().collect(),
             where_clause: ast.generics.where_clause,
         },
         fields,
+        attrs: ast.attrs,
         builder_ident_name,
+        generated_idents: vec![],
+    };
+    from_struct.generated_idents = from_struct.name_generated_items();
+    from_struct
+}
+
+fn camel_case(ident: &Ident) -> String {
+    ident
+        .unraw()
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// An identifier named after `name`, made different from all the `taken` ones.
+fn unique_ident(name: &str, taken: &[Ident]) -> Ident {
+    let mut name = name.to_string();
+    while taken.iter().any(|ident| *ident == name) {
+        name.push('_');
     }
+    format_ident!("{}", name)
 }
 
-fn has_attr_path(attrs: &[Attribute], attr_path: &str) -> bool {
-    attrs
+/// A type identifier named after `name`, made different from all the `taken` ones by a number,
+/// as an underscore would break the camel case.
+fn unique_type_ident(name: &str, taken: &[Ident], span: Span) -> Ident {
+    (1..)
+        .map(|number| match number {
+            1 => name.to_string(),
+            _ => format!("{name}{number}"),
+        })
+        .find(|candidate| !taken.iter().any(|ident| ident == candidate))
+        .map(|candidate| Ident::new(&candidate, span))
+        .unwrap()
+}
+
+/// Type parameters that are the type of a single field, are not mentioned anywhere else, and whose
+/// bounds don't mention other parameters, so that setting the field can change them.
+/// The field must be required, or the parameter must have a default type, that is the type of the
+/// field until it is set.
+fn type_changing_params(generics: &syn::Generics, fields: &[Field]) -> Vec<Ident> {
+    let param_names = generics
+        .params
         .iter()
-        .find(|attr| attr.path().is_ident("builder"))
-        .map(|attr| {
-            let values: Result<Punctuated<Meta, Token![,]>, _> =
-                attr.parse_args_with(Punctuated::parse_terminated);
-
-            match values {
-                Ok(values) => values.iter().any(|m| match m {
-                    Meta::Path(path) => path.is_ident(&format_ident!("{}", attr_path)),
-                    Meta::List(_) => false,
-                    Meta::NameValue(_) => false,
-                }),
-                Err(_) => false,
-            }
+        .filter_map(|param| match param {
+            GenericParam::Lifetime(_) => None,
+            GenericParam::Type(t) => Some(t.ident.clone()),
+            GenericParam::Const(c) => Some(c.ident.clone()),
+        })
+        .collect::<Vec<_>>();
+    let mentions_params = |tokens: TokenStream, except: &Ident| {
+        mentioned_idents(tokens)
+            .iter()
+            .any(|ident| ident != except && param_names.contains(ident))
+    };
+
+    generics
+        .type_params()
+        .filter(|param| {
+            let ident = &param.ident;
+            let mut fields_mentioning = fields
+                .iter()
+                .filter(|field| mentioned_idents(field.ty().to_token_stream()).contains(ident));
+            let single_required_field = fields_mentioning.next().is_some_and(|field| {
+                is_type_param(field.ty(), ident)
+                    && (!field.has_default() || param.default.is_some())
+            }) && fields_mentioning.next().is_none();
+
+            let own_bounds_independent = !mentions_params(param.bounds.to_token_stream(), ident);
+
+            let not_in_other_params = generics
+                .params
+                .iter()
+                .filter(|other| !matches!(other, GenericParam::Type(t) if t.ident == *ident))
+                .all(|other| !mentioned_idents(other.to_token_stream()).contains(ident));
+
+            let where_predicates_independent = generics
+                .where_clause
+                .iter()
+                .flat_map(|clause| clause.predicates.iter())
+                .all(|predicate| {
+                    if bounded_param(predicate) == Some(ident) {
+                        !mentions_params(predicate.to_token_stream(), ident)
+                    } else {
+                        !mentioned_idents(predicate.to_token_stream()).contains(ident)
+                    }
+                });
+
+            single_required_field
+                && own_bounds_independent
+                && not_in_other_params
+                && where_predicates_independent
         })
-        .unwrap_or_default()
+        .map(|param| param.ident.clone())
+        .collect()
+}
+
+fn without_default(param: &GenericParam) -> TokenStream {
+    let mut param = param.clone();
+    match &mut param {
+        GenericParam::Lifetime(_) => {}
+        GenericParam::Type(t) => {
+            t.eq_token = None;
+            t.default = None;
+        }
+        GenericParam::Const(c) => {
+            c.eq_token = None;
+            c.default = None;
+        }
+    }
+    quote! {#param}
+}
+
+fn is_type_param(ty: &Type, param: &Ident) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident(param))
+}
+
+/// The parameter bounded by a `where` predicate like `T: Trait`, if it bounds a bare parameter.
+fn bounded_param(predicate: &WherePredicate) -> Option<&Ident> {
+    match predicate {
+        WherePredicate::Type(predicate) => match &predicate.bounded_ty {
+            Type::Path(path) if path.qself.is_none() => path.path.get_ident(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn has_attr_path(attrs: &[Attribute], attr_path: &str) -> bool {
+    builder_args(attrs).any(|arg| match arg {
+        BuilderArg::Path(path) => path == attr_path,
+        BuilderArg::NameValue(..) => false,
+        BuilderArg::List(..) => false,
+    })
 }
 
 fn get_attr_value(attrs: &[Attribute], key: &str) -> Option<TokenStream> {
+    builder_args(attrs).find_map(|arg| match arg {
+        BuilderArg::Path(_) => None,
+        BuilderArg::NameValue(name, value) => (name == key).then_some(value),
+        BuilderArg::List(..) => None,
+    })
+}
+
+fn get_attr_values(attrs: &[Attribute], key: &str) -> Vec<TokenStream> {
+    builder_args(attrs)
+        .filter_map(|arg| match arg {
+            BuilderArg::Path(_) => None,
+            BuilderArg::NameValue(name, value) => (name == key).then_some(value),
+            BuilderArg::List(..) => None,
+        })
+        .collect()
+}
+
+fn get_attr_lists(attrs: &[Attribute], key: &str) -> Vec<TokenStream> {
+    builder_args(attrs)
+        .filter_map(|arg| match arg {
+            BuilderArg::Path(_) => None,
+            BuilderArg::NameValue(..) => None,
+            BuilderArg::List(name, list) => (name == key).then_some(list),
+        })
+        .collect()
+}
+
+fn serde_attrs(attrs: &[Attribute]) -> Vec<&Attribute> {
     attrs
         .iter()
-        .find(|attr| attr.path().is_ident("builder"))
-        .iter()
-        .find_map(|attr| {
-            let values: Result<Punctuated<Meta, Token![,]>, _> =
-                attr.parse_args_with(Punctuated::parse_terminated);
-
-            match values {
-                Ok(values) => values.iter().find_map(|m| match m {
-                    Meta::Path(_) => None,
-                    Meta::List(_) => None,
-                    Meta::NameValue(nv) => {
-                        if nv.path.is_ident(key) {
-                            Some(nv.value.to_token_stream())
-                        } else {
-                            None
+        .filter(|attr| attr.path().is_ident("serde"))
+        .collect()
+}
+
+fn parse_predicates(tokens: TokenStream) -> Vec<TokenStream> {
+    Punctuated::<WherePredicate, Token![,]>::parse_terminated
+        .parse_str(&lit_str_value(tokens))
+        .unwrap()
+        .into_iter()
+        .map(|predicate| predicate.to_token_stream())
+        .collect()
+}
+
+fn lit_str_value(tokens: TokenStream) -> String {
+    parse2::<LitStr>(tokens).unwrap().value()
+}
+
+fn source_text(tokens: &TokenStream) -> String {
+    let mut text = String::new();
+    let mut previous_end = None;
+    for token in tokens.clone() {
+        let span = token.span();
+        if previous_end.is_some_and(|previous_end| previous_end != span.start()) {
+            text.push(' ');
+        }
+        text.push_str(&span.source_text().unwrap_or_else(|| token.to_string()));
+        previous_end = Some(span.end());
+    }
+    text
+}
+
+fn mentioned_idents(tokens: TokenStream) -> Vec<Ident> {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Ident(ident) => vec![ident],
+            TokenTree::Group(group) => mentioned_idents(group.stream()),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// The variables the expression in `tokens` refers to, leaving out the ones it binds itself, as
+/// closure parameters, `let` statements, `for` loops and patterns do.
+fn referred_variables(tokens: TokenStream) -> Vec<Ident> {
+    match parse2::<Expr>(tokens.clone()) {
+        Ok(expr) => {
+            let mut collector = VariableCollector::default();
+            collector.visit_expr(&expr);
+            collector.variables
+        }
+        Err(_) => mentioned_variables(tokens),
+    }
+}
+
+#[derive(Default)]
+struct VariableCollector {
+    /// The variables bound in the part of the expression being visited.
+    bound: Vec<Ident>,
+    variables: Vec<Ident>,
+}
+
+impl VariableCollector {
+    fn refer(&mut self, ident: &Ident) {
+        if !self.bound.contains(ident) {
+            self.variables.push(ident.clone());
+        }
+    }
+
+    fn bind(&mut self, pat: &syn::Pat) {
+        #[derive(Default)]
+        struct Bindings(Vec<Ident>);
+
+        impl Visit<'_> for Bindings {
+            fn visit_pat_ident(&mut self, pat_ident: &syn::PatIdent) {
+                self.0.push(pat_ident.ident.clone());
+                visit::visit_pat_ident(self, pat_ident);
+            }
+        }
+
+        let mut bindings = Bindings::default();
+        bindings.visit_pat(pat);
+        self.bound.extend(bindings.0);
+    }
+
+    /// Visits with `visit` the part of the expression the bindings made in it are scoped to.
+    fn scoped(&mut self, visit: impl FnOnce(&mut Self)) {
+        let bound_count = self.bound.len();
+        visit(self);
+        self.bound.truncate(bound_count);
+    }
+}
+
+impl Visit<'_> for VariableCollector {
+    fn visit_expr_path(&mut self, expr_path: &syn::ExprPath) {
+        if let (None, Some(ident)) = (&expr_path.qself, expr_path.path.get_ident()) {
+            self.refer(ident);
+        }
+    }
+
+    fn visit_expr_call(&mut self, call: &syn::ExprCall) {
+        // The function called is a function, not a field.
+        if !matches!(&*call.func, Expr::Path(func) if func.path.get_ident().is_some()) {
+            self.visit_expr(&call.func);
+        }
+        call.args.iter().for_each(|arg| self.visit_expr(arg));
+    }
+
+    fn visit_expr_closure(&mut self, closure: &syn::ExprClosure) {
+        self.scoped(|collector| {
+            closure
+                .inputs
+                .iter()
+                .for_each(|input| collector.bind(input));
+            collector.visit_expr(&closure.body);
+        });
+    }
+
+    fn visit_block(&mut self, block: &syn::Block) {
+        self.scoped(|collector| {
+            for stmt in &block.stmts {
+                match stmt {
+                    syn::Stmt::Local(local) => {
+                        if let Some(init) = &local.init {
+                            collector.visit_local_init(init);
                         }
+                        collector.bind(&local.pat);
                     }
-                }),
-                Err(_) => None,
+                    stmt => collector.visit_stmt(stmt),
+                }
+            }
+        });
+    }
+
+    fn visit_arm(&mut self, arm: &syn::Arm) {
+        self.scoped(|collector| {
+            collector.bind(&arm.pat);
+            if let Some((_, guard)) = &arm.guard {
+                collector.visit_expr(guard);
+            }
+            collector.visit_expr(&arm.body);
+        });
+    }
+
+    fn visit_expr_for_loop(&mut self, for_loop: &syn::ExprForLoop) {
+        self.visit_expr(&for_loop.expr);
+        self.scoped(|collector| {
+            collector.bind(&for_loop.pat);
+            collector.visit_block(&for_loop.body);
+        });
+    }
+
+    fn visit_expr_if(&mut self, expr_if: &syn::ExprIf) {
+        self.scoped(|collector| {
+            collector.visit_expr(&expr_if.cond);
+            collector.visit_block(&expr_if.then_branch);
+        });
+        if let Some((_, else_branch)) = &expr_if.else_branch {
+            self.visit_expr(else_branch);
+        }
+    }
+
+    fn visit_expr_while(&mut self, expr_while: &syn::ExprWhile) {
+        self.scoped(|collector| {
+            collector.visit_expr(&expr_while.cond);
+            collector.visit_block(&expr_while.body);
+        });
+    }
+
+    /// The bindings of `if let` and `while let` are scoped by the `if` or `while` expression.
+    fn visit_expr_let(&mut self, expr_let: &syn::ExprLet) {
+        self.visit_expr(&expr_let.expr);
+        self.bind(&expr_let.pat);
+    }
+
+    fn visit_macro(&mut self, mac: &syn::Macro) {
+        mentioned_variables(mac.tokens.clone())
+            .iter()
+            .for_each(|ident| self.refer(ident));
+    }
+}
+
+/// The identifiers of `tokens` that could be variables, leaving out the ones following a `.` or a
+/// `::`, and the ones followed by `(`, `!` or `::`, which are fields, methods, functions, macros or
+/// paths.
+fn mentioned_variables(tokens: TokenStream) -> Vec<Ident> {
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    let is_punct = |index: Option<usize>, chars: &[char]| {
+        index.and_then(|index| tokens.get(index)).is_some_and(
+            |token| matches!(token, TokenTree::Punct(punct) if chars.contains(&punct.as_char())),
+        )
+    };
+    tokens
+        .iter()
+        .enumerate()
+        .flat_map(|(index, token)| match token {
+            TokenTree::Ident(ident) => {
+                let follows_path = is_punct(index.checked_sub(1), &['.', ':']);
+                let is_called = matches!(
+                    tokens.get(index + 1),
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis
+                );
+                let is_path = is_punct(Some(index + 1), &['!', ':']);
+                if follows_path || is_called || is_path {
+                    vec![]
+                } else {
+                    vec![ident.clone()]
+                }
             }
+            TokenTree::Group(group) => mentioned_variables(group.stream()),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// Replaces each of the given parameters by its replacement in `tokens`.
+pub(crate) fn replace_params(tokens: TokenStream, replacements: &[(&Ident, Ident)]) -> TokenStream {
+    replacements
+        .iter()
+        .fold(tokens, |tokens, (param, replacement)| {
+            replace_ident(tokens, &param.to_string(), &quote! {#replacement})
+        })
+}
+
+pub(crate) fn replace_self(tokens: TokenStream, replacement: &TokenStream) -> TokenStream {
+    replace_ident(tokens, "Self", replacement)
+}
+
+fn replace_ident(tokens: TokenStream, name: &str, replacement: &TokenStream) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Ident(ident) if ident == name => replacement.clone(),
+            TokenTree::Group(group) => {
+                let mut replaced = proc_macro2::Group::new(
+                    group.delimiter(),
+                    replace_ident(group.stream(), name, replacement),
+                );
+                replaced.set_span(group.span());
+                TokenTree::Group(replaced).into_token_stream()
+            }
+            other => other.into_token_stream(),
+        })
+        .collect()
+}
+
+enum BuilderArg {
+    Path(Ident),
+    NameValue(Ident, TokenStream),
+    List(Ident, TokenStream),
+}
+
+impl Parse for BuilderArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = Ident::parse_any(input)?;
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Ok(BuilderArg::NameValue(key, parse_arg_value(input)?))
+        } else if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            Ok(BuilderArg::List(key, content.parse()?))
+        } else {
+            Ok(BuilderArg::Path(key))
+        }
+    }
+}
+
+fn parse_arg_value(input: ParseStream) -> syn::Result<TokenStream> {
+    fn ends_value(input: ParseStream) -> bool {
+        input.is_empty() || input.peek(Token![,])
+    }
+
+    let fork = input.fork();
+    if fork.parse::<Expr>().is_ok() && ends_value(&fork) {
+        return Ok(input.parse::<Expr>()?.to_token_stream());
+    }
+    let fork = input.fork();
+    if fork.parse::<Type>().is_ok() && ends_value(&fork) {
+        return Ok(input.parse::<Type>()?.to_token_stream());
+    }
+    let mut value = TokenStream::new();
+    while !ends_value(input) {
+        value.extend([input.parse::<TokenTree>()?]);
+    }
+    Ok(value)
+}
+
+fn builder_args(attrs: &[Attribute]) -> impl Iterator<Item = BuilderArg> + '_ {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("builder"))
+        .flat_map(|attr| {
+            attr.parse_args_with(Punctuated::<BuilderArg, Token![,]>::parse_terminated)
+                .map(|args| args.into_iter().collect::<Vec<_>>())
+                .unwrap_or_default()
         })
 }
@@ -0,0 +1,16 @@
+use type_safe_builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(default)]
+struct Struct {
+    #[builder(conflicts_with = unix_socket)]
+    host: Option<String>,
+    unix_socket: Option<String>,
+}
+
+fn main() {
+    let _ = StructBuilder::builder()
+        .host(Some("localhost".into()))
+        .unix_socket(Some("/tmp/socket".into()))
+        .build();
+}
@@ -0,0 +1,12 @@
+use type_safe_builder_macro::Builder;
+
+#[derive(Builder)]
+struct StructToBuild {
+    #[builder(default = f2 + 1)]
+    f1: i64,
+    #[builder(default = f1 + 1)]
+    f2: i64,
+    f3: i64,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use type_safe_builder_macro::Builder;
+
+struct NoDefault;
+
+#[derive(Builder)]
+struct Struct<T> {
+    #[builder(default)]
+    value: T,
+}
+
+fn main() {
+    let _: Struct<NoDefault> = StructBuilder::builder().build();
+}
@@ -0,0 +1,16 @@
+use type_safe_builder_macro::Builder;
+
+#[derive(Builder)]
+struct Struct {
+    #[builder(group = auth, exactly_one)]
+    password: Option<String>,
+    #[builder(group = auth)]
+    token: Option<String>,
+}
+
+fn main() {
+    let _ = StructBuilder::builder()
+        .password(Some("password".into()))
+        .token(Some("token".into()))
+        .build();
+}
@@ -0,0 +1,13 @@
+use type_safe_builder_macro::Builder;
+
+struct Cert;
+
+#[derive(Builder)]
+struct Struct {
+    #[builder(group = auth, exactly_one)]
+    password: Option<String>,
+    #[builder(group = auth)]
+    cert: Cert,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use type_safe_builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(default)]
+struct Struct {
+    #[builder(requires = tls_key)]
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+}
+
+fn main() {
+    let _ = StructBuilder::builder().tls_cert(Some("cert".into())).build();
+}
@@ -0,0 +1,13 @@
+use type_safe_builder_macro::Builder;
+
+#[derive(Builder)]
+struct Struct {
+    host: String,
+    port: u16,
+    #[builder(default)]
+    user: String,
+}
+
+fn main() {
+    let _ = StructBuilder::builder().port(80).build();
+}
@@ -0,0 +1,22 @@
+use type_safe_builder_macro::Builder;
+
+#[derive(Builder)]
+#[builder(staged)]
+struct Request {
+    method: String,
+    url: String,
+    #[builder(default)]
+    headers: Vec<(String, String)>,
+}
+
+fn main() {
+    let _ = RequestBuilder::builder()
+        .url("http://localhost".into())
+        .method("GET".into())
+        .build();
+    let _ = RequestBuilder::builder()
+        .headers(vec![])
+        .method("GET".into())
+        .url("http://localhost".into())
+        .build();
+}
@@ -403,3 +403,1613 @@ fn if_multi_assignment_is_allowed_by_default_field_can_be_configured_so_it_can_b
     assert_eq!(2, built.f1);
     assert_eq!(3, built.f2);
 }
+
+#[test]
+fn built_value_can_be_converted_into_another_type() {
+    use std::sync::Arc;
+
+    #[derive(Builder)]
+    #[builder(build_into = Arc<Self>)]
+    struct Struct {
+        f1: i64,
+    }
+
+    let built: Arc<Struct> = StructBuilder::builder().f1(1).build();
+
+    assert_eq!(1, built.f1);
+}
+
+#[test]
+fn built_value_of_generic_struct_can_be_converted_into_another_type() {
+    #[derive(Builder)]
+    #[builder(build_into = Box<Self>)]
+    struct GenericStruct<T> {
+        f1: T,
+    }
+
+    let built: Box<GenericStruct<i64>> = GenericStructBuilder::builder().f1(1).build();
+
+    assert_eq!(1, built.f1);
+}
+
+#[test]
+fn post_build_hook_is_called_on_built_value() {
+    fn normalise(built: &mut Struct) {
+        built.f1 = built.f1.to_lowercase();
+    }
+
+    #[derive(Builder)]
+    #[builder(post_build = normalise)]
+    struct Struct {
+        f1: String,
+    }
+
+    let built = StructBuilder::builder().f1("VALUE".into()).build();
+
+    assert_eq!("value", built.f1);
+}
+
+#[test]
+fn post_build_hook_is_called_before_conversion() {
+    use std::rc::Rc;
+
+    fn double(built: &mut Struct) {
+        built.f1 *= 2;
+    }
+
+    #[derive(Builder)]
+    #[builder(post_build = double, build_into = Rc<Self>)]
+    struct Struct {
+        f1: i64,
+    }
+
+    let built: Rc<Struct> = StructBuilder::builder().f1(2).build();
+
+    assert_eq!(4, built.f1);
+}
+
+#[test]
+fn default_values_can_refer_to_other_fields() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(default = format!("{}-{}", slug, port))]
+        display_name: String,
+        #[builder(default = name.to_lowercase())]
+        slug: String,
+        name: String,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    let built = StructBuilder::builder().name("Name".into()).build();
+
+    assert_eq!("name", built.slug);
+    assert_eq!("name-8080", built.display_name);
+}
+
+#[test]
+fn default_values_referring_to_other_fields_use_the_set_values() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        size: usize,
+        #[builder(default = size * 2)]
+        capacity: usize,
+    }
+
+    let built = StructBuilder::builder().size(2).build();
+    assert_eq!(4, built.capacity);
+
+    let built = StructBuilder::builder().size(2).capacity(3).build();
+    assert_eq!(3, built.capacity);
+}
+
+#[test]
+fn default_values_calling_functions_named_after_fields_do_not_refer_to_them() {
+    fn len() -> usize {
+        3
+    }
+    fn first() -> usize {
+        second() + 1
+    }
+    fn second() -> usize {
+        1
+    }
+
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        len: usize,
+        #[builder(default = len())]
+        cap: usize,
+        #[builder(default = second())]
+        first: usize,
+        #[builder(default = first() + "name".len())]
+        second: usize,
+    }
+
+    let built = StructBuilder::builder().len(1).build();
+
+    assert_eq!(1, built.len);
+    assert_eq!(3, built.cap);
+    assert_eq!(1, built.first);
+    assert_eq!(6, built.second);
+}
+
+#[test]
+fn default_values_binding_names_of_fields_do_not_refer_to_them() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(default = [1u16].iter().map(|b| b + 1).sum())]
+        a: u16,
+        #[builder(default = a + 1)]
+        b: u16,
+        #[builder(default = { let a = 10; match Some(a) { Some(b) => b + 1, None => 0 } })]
+        c: u16,
+    }
+
+    let built = StructBuilder::builder().c(1).build();
+
+    assert_eq!(2, built.a);
+    assert_eq!(3, built.b);
+    assert_eq!(1, built.c);
+
+    let built = StructBuilder::builder().build();
+
+    assert_eq!(11, built.c);
+}
+
+#[test]
+fn default_values_are_not_evaluated_if_field_is_set() {
+    use std::cell::Cell;
+
+    thread_local! {
+        static EVALUATED: Cell<bool> = const { Cell::new(false) };
+    }
+    fn expensive_default() -> i64 {
+        EVALUATED.set(true);
+        10
+    }
+
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(default = expensive_default())]
+        f1: i64,
+    }
+
+    let built = StructBuilder::builder().f1(1).build();
+
+    assert_eq!(1, built.f1);
+    assert!(!EVALUATED.get());
+
+    let built = StructBuilder::builder().build();
+
+    assert_eq!(10, built.f1);
+    assert!(EVALUATED.get());
+}
+
+#[test]
+fn default_values_can_be_set_with_function() {
+    fn default_f1() -> Vec<i64> {
+        vec![1, 2, 3]
+    }
+
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(default_with = default_f1)]
+        f1: Vec<i64>,
+    }
+
+    let built = StructBuilder::builder().build();
+    assert_eq!(vec![1, 2, 3], built.f1);
+
+    let built = StructBuilder::builder().f1(vec![4]).build();
+    assert_eq!(vec![4], built.f1);
+}
+
+#[test]
+fn built_struct_can_be_converted_back_into_builder() {
+    #[derive(Builder)]
+    #[builder(to_builder)]
+    struct Struct {
+        host: String,
+        #[builder(multi)]
+        port: u16,
+    }
+
+    let built = StructBuilder::builder()
+        .host("localhost".into())
+        .port(80)
+        .build();
+
+    let rebuilt = built.to_builder().port(8080).build();
+
+    assert_eq!("localhost", rebuilt.host);
+    assert_eq!(8080, rebuilt.port);
+}
+
+#[test]
+fn fields_set_once_can_be_overridden_in_builder_converted_back() {
+    #[derive(Builder)]
+    #[builder(to_builder)]
+    struct Struct<T> {
+        host: String,
+        #[builder(setter_name = with_port)]
+        port: u16,
+        tag: T,
+    }
+
+    let built = StructBuilder::builder()
+        .host("localhost".into())
+        .with_port(80)
+        .tag(1u8)
+        .build();
+
+    let rebuilt = built
+        .to_builder()
+        .override_with_port(8080)
+        .override_tag("tag")
+        .build();
+
+    assert_eq!("localhost", rebuilt.host);
+    assert_eq!(8080, rebuilt.port);
+    assert_eq!("tag", rebuilt.tag);
+}
+
+#[test]
+fn struct_can_have_its_own_to_builder_method() {
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(multi)]
+        port: u16,
+    }
+
+    impl Struct {
+        fn to_builder(&self) -> u16 {
+            self.port
+        }
+    }
+
+    let built = StructBuilder::builder().port(80).build();
+    assert_eq!(80, built.to_builder());
+
+    let rebuilt = StructBuilderState::from(built).port(8080).build();
+    assert_eq!(8080, rebuilt.port);
+}
+
+#[test]
+fn builder_can_be_created_from_generic_struct() {
+    #[derive(Builder)]
+    struct GenericStruct<'a, T> {
+        f1: &'a T,
+        #[builder(multi, default)]
+        f2: T,
+    }
+
+    let value = 1;
+    let built = GenericStructBuilder::builder().f1(&value).build();
+
+    let builder: GenericStructBuilderState<
+        i64,
+        GenericStructBuilderF1Set,
+        GenericStructBuilderF2Set,
+    > = built.into();
+    let rebuilt = builder.f2(2).build();
+
+    assert_eq!(1, *rebuilt.f1);
+    assert_eq!(2, rebuilt.f2);
+}
+
+#[test]
+fn struct_default_impl_can_be_used_for_fields_not_set() {
+    #[derive(Builder)]
+    #[builder(default = struct)]
+    struct Struct {
+        host: String,
+        port: u16,
+        #[builder(default = 10)]
+        timeout: u64,
+    }
+
+    impl Default for Struct {
+        fn default() -> Self {
+            Struct {
+                host: "localhost".into(),
+                port: 8080,
+                timeout: 30,
+            }
+        }
+    }
+
+    let built = StructBuilder::builder().port(80).build();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(80, built.port);
+    assert_eq!(10, built.timeout);
+}
+
+#[test]
+fn struct_default_impl_is_only_called_once_when_a_field_is_not_set() {
+    use std::cell::Cell;
+
+    thread_local! {
+        static CALLS: Cell<u32> = const { Cell::new(0) };
+    }
+
+    #[derive(Builder)]
+    #[builder(default = struct)]
+    struct Struct {
+        host: String,
+        port: u16,
+    }
+
+    impl Default for Struct {
+        fn default() -> Self {
+            CALLS.set(CALLS.get() + 1);
+            Struct {
+                host: "localhost".into(),
+                port: 8080,
+            }
+        }
+    }
+
+    let built = StructBuilder::builder()
+        .host("example.com".into())
+        .port(80)
+        .build();
+
+    assert_eq!("example.com", built.host);
+    assert_eq!(0, CALLS.get());
+
+    let built = StructBuilder::builder().build();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+    assert_eq!(1, CALLS.get());
+}
+
+#[test]
+fn struct_default_impl_can_be_used_with_mandatory_fields() {
+    #[derive(Default, Builder)]
+    #[builder(default = struct)]
+    struct GenericStruct<T> {
+        #[builder(mandatory)]
+        f1: T,
+        f2: T,
+    }
+
+    let built = GenericStructBuilder::builder().f1(1).build();
+
+    assert_eq!(1, built.f1);
+    assert_eq!(0, built.f2);
+}
+
+#[test]
+fn methods_of_builder_states_are_renamed_when_a_setter_has_their_name() {
+    #[derive(Builder)]
+    struct Struct {
+        build_over: bool,
+        #[builder(default)]
+        is_set: bool,
+        #[builder(default)]
+        override_port: u16,
+        port: u16,
+    }
+
+    let base = StructBuilder::builder().build_over(true).port(80).build();
+    let built = StructBuilder::builder()
+        .port(8080)
+        .override_port(1)
+        .override_port_(8000)
+        .is_set(true)
+        .build_over_(base);
+
+    assert!(built.build_over);
+    assert!(built.is_set);
+    assert_eq!(1, built.override_port);
+    assert_eq!(8000, built.port);
+    assert!(StructBuilder::builder().port(80).is_set_("port"));
+}
+
+#[test]
+fn builder_can_be_built_over_a_base_value() {
+    #[derive(Builder)]
+    struct Struct {
+        host: String,
+        port: u16,
+    }
+
+    let base = StructBuilder::builder()
+        .host("localhost".into())
+        .port(80)
+        .build();
+
+    let built = StructBuilder::builder().port(8080).build_over(base);
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+}
+
+#[test]
+fn generic_fields_not_set_take_their_type_from_the_base_value() {
+    #[derive(Builder)]
+    struct Struct<T: Clone, U = u8> {
+        n: i32,
+        value: T,
+        #[builder(default)]
+        other: U,
+    }
+
+    let base = Struct {
+        n: 1,
+        value: String::from("base"),
+        other: 'b',
+    };
+
+    let built = StructBuilder::builder().n(2).build_over(base);
+
+    assert_eq!(2, built.n);
+    assert_eq!("base", built.value);
+    assert_eq!('b', built.other);
+
+    let base = Struct {
+        n: 1,
+        value: String::from("base"),
+        other: 4u64,
+    };
+
+    let built = StructBuilder::builder()
+        .value(String::from("set"))
+        .other(3u64)
+        .build_over(base);
+
+    assert_eq!(1, built.n);
+    assert_eq!("set", built.value);
+    assert_eq!(3, built.other);
+}
+
+#[test]
+fn derives_can_be_added_to_builder_state() {
+    #[derive(Builder)]
+    #[builder(derive(Clone, Debug, PartialEq))]
+    struct GenericStruct<'a, T> {
+        host: &'a str,
+        port: T,
+    }
+
+    let template = GenericStructBuilder::builder().host("localhost");
+
+    assert_eq!(template.clone(), template);
+    assert!(format!("{:?}", template).contains("localhost"));
+
+    let built1 = template.clone().port(80).build();
+    let built2 = template.port(8080).build();
+
+    assert_eq!(80, built1.port);
+    assert_eq!(8080, built2.port);
+}
+
+#[test]
+fn attributes_can_be_added_to_builder_state_and_factory() {
+    #[derive(Builder)]
+    #[builder(
+        state_attr(derive(Clone)),
+        state_attr(must_use),
+        factory_attr(derive(Debug))
+    )]
+    struct Struct {
+        f1: i64,
+    }
+
+    let template = StructBuilder::builder();
+    let built = template.clone().f1(1).build();
+
+    assert_eq!(1, built.f1);
+    assert_eq!("StructBuilder", format!("{:?}", StructBuilder {}));
+}
+
+#[test]
+fn debug_shows_state_of_each_field() {
+    #[derive(Builder)]
+    #[builder(derive(Debug))]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+        #[builder(default)]
+        timeout: u64,
+        #[builder(default = host.to_uppercase())]
+        name: String,
+        #[builder(debug = redact)]
+        password: String,
+        user: String,
+    }
+
+    let builder = StructBuilder::builder()
+        .host("localhost".into())
+        .password("secret".into());
+
+    assert_eq!(
+        r#"Struct { host: "localhost", port: <default: 8080>, timeout: <default>, name: <default: host.to_uppercase()>, password: <redacted>, user: <unset> }"#,
+        format!("{:?}", builder)
+    );
+}
+
+#[test]
+fn dynamic_builder_can_build_struct() {
+    #[derive(Builder)]
+    #[builder(dynamic)]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+        #[builder(multi, setter_name = add_tag)]
+        tag: String,
+    }
+
+    let mut builder = StructDynBuilder::new();
+    builder.host("localhost".into()).unwrap();
+    for tag in ["a", "b"] {
+        builder.add_tag(tag.into());
+    }
+
+    let built = builder.try_build().unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+    assert_eq!("b", built.tag);
+}
+
+#[test]
+fn dynamic_builder_reports_missing_fields() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(dynamic)]
+    struct Struct {
+        host: String,
+        #[builder(default)]
+        port: u16,
+        user: String,
+    }
+
+    let result = StructDynBuilder::new().try_build();
+
+    assert_eq!(
+        StructDynBuilderError::MissingFields(vec!["host", "user"]),
+        result.unwrap_err()
+    );
+}
+
+#[test]
+fn dynamic_builder_does_not_allow_single_fields_to_be_set_twice() {
+    #[derive(Builder)]
+    #[builder(dynamic)]
+    struct Struct {
+        host: String,
+    }
+
+    let mut builder = StructDynBuilder::new();
+    builder.host("localhost".into()).unwrap();
+    let result = builder.host("example.com".into()).map(|_| ());
+
+    assert_eq!(Err(StructDynBuilderError::AlreadySet("host")), result);
+    assert_eq!("localhost", builder.try_build().unwrap().host);
+}
+
+#[test]
+fn typestate_builder_can_be_converted_into_dynamic_builder() {
+    #[derive(Builder)]
+    #[builder(dynamic)]
+    struct GenericStruct<T> {
+        f1: T,
+        f2: T,
+    }
+
+    let mut builder: GenericStructDynBuilder<i64> = GenericStructBuilder::builder().f1(1).into();
+    builder.f2(2).unwrap();
+
+    let built = builder.try_build().unwrap();
+
+    assert_eq!(1, built.f1);
+    assert_eq!(2, built.f2);
+}
+
+#[test]
+fn builder_exposes_fields_info() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080, setter_name = with_port)]
+        port: u16,
+        #[builder(multi, default)]
+        tags: Vec<String>,
+    }
+
+    assert_eq!(
+        &[
+            StructBuilderFieldInfo {
+                name: "host",
+                setter_name: "host",
+                type_name: "String",
+                required: true,
+                multi: false,
+                default_value: None,
+            },
+            StructBuilderFieldInfo {
+                name: "port",
+                setter_name: "with_port",
+                type_name: "u16",
+                required: false,
+                multi: false,
+                default_value: Some("8080"),
+            },
+            StructBuilderFieldInfo {
+                name: "tags",
+                setter_name: "tags",
+                type_name: "Vec<String>",
+                required: false,
+                multi: true,
+                default_value: None,
+            },
+        ],
+        StructBuilder::FIELDS
+    );
+}
+
+#[test]
+fn builder_state_exposes_which_fields_are_set() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        host: String,
+        port: u16,
+    }
+
+    let builder = StructBuilder::builder().port(80);
+
+    assert!(!builder.is_set("host"));
+    assert!(builder.is_set("port"));
+    assert!(!builder.is_set("unknown"));
+}
+
+#[test]
+fn dynamic_builder_fields_can_be_set_by_name() {
+    #[derive(Builder)]
+    #[builder(from_pairs)]
+    struct Struct {
+        host: String,
+        #[builder(setter_name = with_port)]
+        port: u16,
+    }
+
+    let mut builder = StructDynBuilder::new();
+    builder.set_by_name("host", "localhost").unwrap();
+    builder.set_by_name("with_port", "80").unwrap();
+
+    let built = builder.try_build().unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(80, built.port);
+}
+
+#[test]
+fn struct_can_be_built_from_pairs() {
+    #[derive(Builder)]
+    #[builder(from_pairs)]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    let built = StructBuilder::from_pairs([("host", "localhost")]).unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+}
+
+#[test]
+fn building_from_pairs_reports_errors_with_field_names() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(from_pairs)]
+    struct Struct {
+        host: String,
+        port: u16,
+    }
+
+    assert_eq!(
+        StructDynBuilderError::UnknownField("hots".into()),
+        StructBuilder::from_pairs([("hots", "localhost")]).unwrap_err()
+    );
+    assert_eq!(
+        StructDynBuilderError::InvalidValue {
+            field: "port",
+            message: "invalid digit found in string".into()
+        },
+        StructBuilder::from_pairs([("port", "eighty")]).unwrap_err()
+    );
+    assert_eq!(
+        StructDynBuilderError::MissingFields(vec!["port"]),
+        StructBuilder::from_pairs([("host", "localhost")]).unwrap_err()
+    );
+    assert_eq!(
+        StructDynBuilderError::AlreadySet("host"),
+        StructBuilder::from_pairs([("host", "localhost"), ("host", "example.com")]).unwrap_err()
+    );
+}
+
+#[test]
+fn fields_skipped_from_pairs_need_not_be_parsable() {
+    #[derive(Builder, Debug)]
+    #[builder(from_pairs)]
+    struct Struct {
+        host: String,
+        #[builder(skip_pairs, default)]
+        aliases: Vec<String>,
+    }
+
+    let built = StructBuilder::from_pairs([("host", "localhost")]).unwrap();
+    assert_eq!("localhost", built.host);
+    assert!(built.aliases.is_empty());
+
+    let mut builder = StructDynBuilder::new();
+    assert_eq!(
+        Err(StructDynBuilderError::UnknownField("aliases".into())),
+        builder.set_by_name("aliases", "local")
+    );
+    builder.aliases(vec!["local".into()]).unwrap();
+    builder.set_by_name("host", "localhost").unwrap();
+    assert_eq!(vec!["local"], builder.try_build().unwrap().aliases);
+}
+
+#[test]
+fn struct_can_be_built_from_env() {
+    #[derive(Builder)]
+    #[builder(env_prefix = "TYPE_SAFE_BUILDER_TEST_")]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+        #[builder(env = "TYPE_SAFE_BUILDER_TEST_DATABASE_URL")]
+        url: String,
+    }
+
+    // SAFETY: no other test reads or writes these variables
+    unsafe {
+        std::env::set_var("TYPE_SAFE_BUILDER_TEST_HOST", "localhost");
+        std::env::set_var("TYPE_SAFE_BUILDER_TEST_DATABASE_URL", "postgres://db");
+    }
+
+    let built = StructBuilder::from_env().unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+    assert_eq!("postgres://db", built.url);
+}
+
+#[test]
+fn fields_skipped_from_env_need_not_be_parsable() {
+    #[derive(Builder)]
+    #[builder(env_prefix = "APP_")]
+    struct Struct {
+        host: String,
+        #[builder(skip_env, default)]
+        tags: Vec<String>,
+    }
+
+    let built = StructBuilder::from_env_with(|name| match name {
+        "APP_HOST" => Some("localhost".into()),
+        "APP_TAGS" => Some("tag".into()),
+        _ => None,
+    })
+    .unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert!(built.tags.is_empty());
+}
+
+#[test]
+fn struct_can_be_built_from_injected_env() {
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(env = "HOST")]
+        host: String,
+        #[builder(env = "PORT", default = 8080)]
+        port: u16,
+        #[builder(default)]
+        user: String,
+    }
+
+    let built = StructBuilder::from_env_with(|name| match name {
+        "HOST" => Some("localhost".into()),
+        "PORT" => Some("80".into()),
+        _ => None,
+    })
+    .unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(80, built.port);
+    assert_eq!("", built.user);
+}
+
+#[test]
+fn building_from_env_reports_all_missing_and_invalid_fields() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(env_prefix = "APP_")]
+    struct Struct {
+        host: String,
+        port: u16,
+        #[builder(default)]
+        timeout: u64,
+        user: String,
+    }
+
+    let error = StructBuilder::from_env_with(|name| match name {
+        "APP_PORT" => Some("eighty".into()),
+        "APP_TIMEOUT" => Some("-1".into()),
+        _ => None,
+    })
+    .unwrap_err();
+
+    assert_eq!(
+        StructBuilderEnvError {
+            missing_fields: vec!["host", "user"],
+            invalid_fields: vec![
+                ("port", "invalid digit found in string".into()),
+                ("timeout", "invalid digit found in string".into()),
+            ],
+            violated_groups: vec![],
+            violated_relations: vec![],
+        },
+        error
+    );
+}
+
+#[test]
+fn partials_can_be_merged_and_built() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(partial)]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+        #[builder(default)]
+        user: String,
+    }
+
+    let defaults = StructPartial {
+        host: Some("localhost".into()),
+        user: Some("admin".into()),
+        ..StructPartial::new()
+    };
+    let file = StructPartial {
+        host: Some("example.com".into()),
+        ..StructPartial::new()
+    };
+    let cli = StructPartial {
+        port: Some(80),
+        ..StructPartial::new()
+    };
+
+    let built = defaults.merge(file).merge(cli).try_build().unwrap();
+
+    assert_eq!("example.com", built.host);
+    assert_eq!(80, built.port);
+    assert_eq!("admin", built.user);
+}
+
+#[test]
+fn partial_reports_missing_required_fields() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(partial, partial_attr(derive(Clone, Debug, PartialEq)))]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    let partial = StructPartial {
+        port: Some(80),
+        ..StructPartial::new()
+    };
+    assert_eq!(partial.clone(), partial);
+
+    let mut builder = partial.into_builder();
+    assert_eq!(
+        StructDynBuilderError::MissingFields(vec!["host"]),
+        StructPartial::new().try_build().unwrap_err()
+    );
+    builder.host("localhost".into()).unwrap();
+    assert_eq!(80, builder.try_build().unwrap().port);
+}
+
+#[test]
+fn exactly_one_field_of_a_group_can_be_set() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        user: String,
+        #[builder(group = auth, exactly_one)]
+        password: Option<String>,
+        #[builder(group = auth)]
+        token: Option<String>,
+        #[builder(group = auth)]
+        cert: Option<Vec<u8>>,
+    }
+
+    let built = StructBuilder::builder()
+        .token(Some("token".into()))
+        .user("user".into())
+        .build();
+
+    assert_eq!(None, built.password);
+    assert_eq!(Some("token".into()), built.token);
+    assert_eq!(None, built.cert);
+}
+
+#[test]
+fn built_struct_with_exclusive_group_can_be_converted_back_into_builder() {
+    #[derive(Builder)]
+    #[builder(to_builder)]
+    struct Struct {
+        user: String,
+        #[builder(group = auth, exactly_one)]
+        password: Option<String>,
+        #[builder(group = auth)]
+        token: Option<String>,
+    }
+
+    let built = StructBuilder::builder()
+        .token(Some("token".into()))
+        .user("user".into())
+        .build();
+
+    let rebuilt = built
+        .to_builder()
+        .override_user("other".into())
+        .override_token(Some("other".into()))
+        .build();
+
+    assert_eq!("other", rebuilt.user);
+    assert_eq!(None, rebuilt.password);
+    assert_eq!(Some("other".into()), rebuilt.token);
+}
+
+#[test]
+fn fields_of_non_exclusive_groups_can_be_combined() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(group = ip, at_least_one)]
+        ipv4: Option<String>,
+        #[builder(group = ip)]
+        ipv6: Option<String>,
+        #[builder(group = proxy, at_most_one)]
+        http_proxy: Option<String>,
+        #[builder(group = proxy)]
+        socks_proxy: Option<String>,
+    }
+
+    let built = StructBuilder::builder()
+        .ipv4(Some("127.0.0.1".into()))
+        .ipv6(Some("::1".into()))
+        .build();
+
+    assert_eq!(Some("127.0.0.1".into()), built.ipv4);
+    assert_eq!(Some("::1".into()), built.ipv6);
+    assert_eq!(None, built.http_proxy);
+    assert_eq!(None, built.socks_proxy);
+}
+
+#[test]
+fn groups_can_have_many_members() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    #[builder(default)]
+    struct Struct {
+        #[builder(group = large, at_least_one)]
+        m0: Option<u8>,
+        #[builder(group = large)]
+        m1: Option<u8>,
+        #[builder(group = large)]
+        m2: Option<u8>,
+        #[builder(group = large)]
+        m3: Option<u8>,
+        #[builder(group = large)]
+        m4: Option<u8>,
+        #[builder(group = large)]
+        m5: Option<u8>,
+        #[builder(group = large)]
+        m6: Option<u8>,
+        #[builder(group = large)]
+        m7: Option<u8>,
+        #[builder(group = large)]
+        m8: Option<u8>,
+        #[builder(group = large)]
+        m9: Option<u8>,
+        #[builder(group = large)]
+        m10: Option<u8>,
+        #[builder(group = large)]
+        m11: Option<u8>,
+        #[builder(group = large)]
+        m12: Option<u8>,
+        #[builder(group = large)]
+        m13: Option<u8>,
+        #[builder(group = large)]
+        m14: Option<u8>,
+        #[builder(group = large)]
+        m15: Option<u8>,
+        #[builder(group = large)]
+        m16: Option<u8>,
+        #[builder(group = large)]
+        m17: Option<u8>,
+        #[builder(group = large)]
+        m18: Option<u8>,
+        #[builder(group = large)]
+        m19: Option<u8>,
+    }
+
+    let built = StructBuilder::builder().m19(Some(19)).m3(Some(3)).build();
+
+    assert_eq!(Some(19), built.m19);
+    assert_eq!(Some(3), built.m3);
+    assert_eq!(None, built.m0);
+}
+
+#[test]
+fn dynamic_builder_reports_violated_group() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(dynamic)]
+    struct Struct {
+        #[builder(group = auth, exactly_one)]
+        password: Option<String>,
+        #[builder(group = auth)]
+        token: Option<String>,
+    }
+
+    let mut builder = StructDynBuilder::new();
+    builder.password(Some("password".into())).unwrap();
+    builder.token(Some("token".into())).unwrap();
+
+    assert_eq!(
+        StructDynBuilderError::GroupRuleViolated {
+            group: "auth",
+            rule: "exactly one"
+        },
+        builder.try_build().unwrap_err()
+    );
+    assert_eq!(
+        "exactly one field of group auth must be set",
+        StructDynBuilder::new().try_build().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn fields_can_require_or_conflict_with_other_fields() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct {
+        #[builder(default, requires = tls_key)]
+        tls_cert: Option<String>,
+        #[builder(default)]
+        tls_key: Option<String>,
+        #[builder(default, conflicts_with = unix_socket)]
+        host: Option<String>,
+        #[builder(default)]
+        unix_socket: Option<String>,
+    }
+
+    let built = StructBuilder::builder()
+        .tls_cert(Some("cert".into()))
+        .tls_key(Some("key".into()))
+        .host(Some("localhost".into()))
+        .build();
+
+    assert_eq!(Some("cert".into()), built.tls_cert);
+    assert_eq!(Some("key".into()), built.tls_key);
+    assert_eq!(Some("localhost".into()), built.host);
+
+    let built = StructBuilder::builder()
+        .tls_key(Some("key".into()))
+        .unix_socket(Some("/tmp/socket".into()))
+        .build();
+
+    assert_eq!(None, built.tls_cert);
+    assert_eq!(Some("/tmp/socket".into()), built.unix_socket);
+}
+
+#[test]
+fn built_struct_with_conflicting_fields_can_be_converted_back_into_builder() {
+    #[derive(Builder)]
+    #[builder(to_builder)]
+    struct Struct {
+        #[builder(default, conflicts_with = unix_socket)]
+        host: Option<String>,
+        #[builder(default)]
+        unix_socket: Option<String>,
+        #[builder(default)]
+        port: u16,
+    }
+
+    let built = StructBuilder::builder()
+        .host(Some("localhost".into()))
+        .build();
+
+    let rebuilt = built
+        .to_builder()
+        .override_host(Some("example.com".into()))
+        .override_port(8080)
+        .build();
+
+    assert_eq!(Some("example.com".into()), rebuilt.host);
+    assert_eq!(None, rebuilt.unix_socket);
+    assert_eq!(8080, rebuilt.port);
+}
+
+#[test]
+fn dynamic_builder_reports_violated_relation() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(dynamic, default)]
+    struct Struct {
+        #[builder(requires = tls_key)]
+        tls_cert: Option<String>,
+        tls_key: Option<String>,
+    }
+
+    let mut builder = StructDynBuilder::new();
+    builder.tls_cert(Some("cert".into())).unwrap();
+
+    assert_eq!(
+        StructDynBuilderError::RelationViolated {
+            field: "tls_cert",
+            relation: "requires",
+            other: "tls_key"
+        },
+        builder.try_build().unwrap_err()
+    );
+}
+
+#[test]
+fn staged_builder_sets_required_fields_in_order() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    #[builder(staged)]
+    struct Request {
+        method: String,
+        url: String,
+        #[builder(default)]
+        headers: Vec<(String, String)>,
+        body: String,
+        #[builder(default)]
+        timeout: u64,
+    }
+
+    let built = RequestBuilder::builder()
+        .method("POST".into())
+        .url("http://localhost".into())
+        .headers(vec![("Accept".into(), "*/*".into())])
+        .body("{}".into())
+        .timeout(10)
+        .build();
+
+    assert_eq!("POST", built.method);
+    assert_eq!("http://localhost", built.url);
+    assert_eq!(1, built.headers.len());
+    assert_eq!("{}", built.body);
+    assert_eq!(10, built.timeout);
+}
+
+#[test]
+fn setting_a_generic_field_chooses_its_type() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct<H: Fn(i32) -> i32> {
+        name: String,
+        handler: H,
+    }
+
+    let builder = StructBuilder::builder().name("double".into());
+    let built = builder.handler(|value| value * 2).build();
+
+    assert_eq!("double", built.name);
+    assert_eq!(4, (built.handler)(2));
+}
+
+#[test]
+fn setting_again_a_generic_field_changes_its_type() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Struct<T>
+    where
+        T: ToString,
+    {
+        #[builder(multi)]
+        value: T,
+    }
+
+    let built = StructBuilder::builder().value(1).value("one").build();
+
+    assert_eq!("one", built.value.to_string());
+}
+
+#[test]
+fn generic_fields_with_default_type_can_keep_their_default() {
+    trait Logger {
+        fn log(&self, message: &str) -> String;
+    }
+
+    #[derive(Default)]
+    struct NoopLogger;
+    impl Logger for NoopLogger {
+        fn log(&self, _: &str) -> String {
+            String::new()
+        }
+    }
+
+    struct PrefixLogger(&'static str);
+    impl Logger for PrefixLogger {
+        fn log(&self, message: &str) -> String {
+            format!("{}{}", self.0, message)
+        }
+    }
+
+    #[derive(Default)]
+    struct TcpConn;
+
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Service<L: Logger = NoopLogger, C = TcpConn> {
+        name: String,
+        #[builder(default = NoopLogger)]
+        logger: L,
+        #[builder(default)]
+        conn: C,
+    }
+
+    let built = ServiceBuilder::builder().name("service".into()).build();
+    assert_eq!("", built.logger.log("started"));
+
+    let built = ServiceBuilder::builder()
+        .logger(PrefixLogger("service: "))
+        .name("service".into())
+        .conn(42)
+        .build();
+    assert_eq!("service: started", built.logger.log("started"));
+    assert_eq!(42, built.conn);
+}
+
+#[test]
+fn can_derive_builder_for_struct_with_default_const_parameter() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Buffer<const N: usize = 64> {
+        data: [u8; N],
+        #[builder(default)]
+        len: usize,
+    }
+
+    let built = BufferBuilder::builder().data([0; 64]).build();
+    let _: Buffer = built;
+
+    let built = BufferBuilder::builder().data([1; 4]).len(4).build();
+    assert_eq!([1; 4], built.data);
+    assert_eq!(4, built.len);
+}
+
+#[test]
+fn self_in_field_types_and_default_values_means_the_struct() {
+    #[allow(dead_code)]
+    #[derive(Builder)]
+    struct Tree<T> {
+        value: T,
+        #[builder(default = Self::no_children())]
+        children: Vec<Box<Self>>,
+        #[builder(default_with = Self::no_parent)]
+        parent: Option<Box<Self>>,
+    }
+
+    impl<T> Tree<T> {
+        fn no_children() -> Vec<Box<Self>> {
+            vec![]
+        }
+
+        fn no_parent() -> Option<Box<Self>> {
+            None
+        }
+    }
+
+    let leaf = TreeBuilder::builder().value(2).build();
+    let built = TreeBuilder::builder()
+        .value(1)
+        .children(vec![Box::new(leaf)])
+        .build();
+
+    assert_eq!(1, built.value);
+    assert_eq!(2, built.children[0].value);
+    assert!(built.children[0].children.is_empty());
+    assert!(built.parent.is_none());
+}
+
+#[test]
+fn can_derive_builder_for_defaulted_generic_field() {
+    #[derive(Builder)]
+    struct Struct<T> {
+        #[builder(default)]
+        value: T,
+    }
+
+    let built: Struct<String> = StructBuilder::builder().build();
+    assert_eq!("", built.value);
+
+    let built = StructBuilder::builder().value(1).build();
+    assert_eq!(1, built.value);
+}
+
+#[test]
+fn can_override_inferred_bounds() {
+    #[derive(Builder)]
+    struct Struct<T> {
+        #[builder(default, bound = "Vec<T>: Default")]
+        values: Vec<T>,
+        #[builder(default)]
+        value: Option<T>,
+    }
+
+    struct NoDefault;
+
+    let built: Struct<NoDefault> = StructBuilder::builder().build();
+    assert!(built.values.is_empty());
+    assert!(built.value.is_none());
+}
+
+#[test]
+fn can_override_all_inferred_bounds_of_the_struct() {
+    #[derive(Builder)]
+    #[builder(bound = "T: Default + Clone")]
+    struct Struct<T> {
+        #[builder(default)]
+        value: T,
+    }
+
+    let built: Struct<String> = StructBuilder::builder().build();
+    assert_eq!("", built.value);
+}
+
+#[test]
+fn cfg_and_cfg_attr_on_fields_are_honoured() {
+    #[derive(Builder)]
+    struct Struct {
+        #[cfg(not(test))]
+        disabled: NotExistingType,
+        #[cfg(test)]
+        enabled: i32,
+        #[cfg_attr(test, builder(default))]
+        defaulted: i32,
+        #[cfg_attr(not(test), builder(default))]
+        required: i32,
+    }
+
+    let built = StructBuilder::builder().enabled(1).required(2).build();
+
+    assert_eq!(1, built.enabled);
+    assert_eq!(0, built.defaulted);
+    assert_eq!(2, built.required);
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn fields_with_names_colliding_once_uppercased_or_raw_can_be_set() {
+    #[derive(Builder)]
+    #[builder(dynamic, from_pairs)]
+    struct Struct {
+        foo: i32,
+        FOO: i32,
+        r#type: i32,
+    }
+
+    let built = StructBuilder::builder().foo(1).FOO(2).r#type(3).build();
+
+    assert_eq!(1, built.foo);
+    assert_eq!(2, built.FOO);
+    assert_eq!(3, built.r#type);
+    assert_eq!("type", StructBuilder::FIELDS[2].name);
+
+    let built = StructBuilder::from_pairs([("foo", "1"), ("FOO", "2"), ("type", "3")]).unwrap();
+
+    assert_eq!(3, built.r#type);
+}
+
+#[test]
+fn generic_parameters_named_as_internal_ones_do_not_collide() {
+    #[allow(non_camel_case_types)]
+    #[derive(Builder)]
+    struct Struct<PLACEHOLDERXTYPE, T, NewT: Default> {
+        x: PLACEHOLDERXTYPE,
+        t: T,
+        #[builder(default)]
+        new_t: NewT,
+    }
+
+    let built: Struct<i32, &str, u8> = StructBuilder::builder().x(1).t("t").build();
+
+    assert_eq!(1, built.x);
+    assert_eq!("t", built.t);
+    assert_eq!(0, built.new_t);
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn fields_with_names_colliding_once_camel_cased_can_be_set() {
+    #[derive(Builder)]
+    struct Struct {
+        foo: i32,
+        _foo: i32,
+        foo_is: i32,
+        a_b: i32,
+        A_b: i32,
+        #[builder(requires = a_b)]
+        foo_bar: i32,
+        #[builder(default, requires = A_b)]
+        foo__bar: i32,
+    }
+
+    let built = StructBuilder::builder()
+        .foo(1)
+        ._foo(2)
+        .foo_is(3)
+        .a_b(4)
+        .A_b(5)
+        .foo_bar(6)
+        .foo__bar(7)
+        .build();
+
+    assert_eq!(1, built.foo);
+    assert_eq!(2, built._foo);
+    assert_eq!(3, built.foo_is);
+    assert_eq!(4, built.a_b);
+    assert_eq!(5, built.A_b);
+    assert_eq!(6, built.foo_bar);
+    assert_eq!(7, built.foo__bar);
+}
+
+#[test]
+fn builder_states_could_be_named_with_aliases() {
+    #[derive(Builder)]
+    struct Struct<T> {
+        host: String,
+        value: T,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    struct Holder {
+        builder: StructBuilderEmpty,
+    }
+
+    fn configured() -> StructBuilderReady<i32> {
+        StructBuilder::builder().host("localhost".into()).value(1)
+    }
+
+    let holder = Holder {
+        builder: StructBuilder::builder(),
+    };
+    let built = holder.builder.host("host".into()).value(2).port(80).build();
+    assert_eq!("host", built.host);
+    assert_eq!(2, built.value);
+    assert_eq!(80, built.port);
+
+    let built = configured().build();
+    assert_eq!("localhost", built.host);
+    assert_eq!(1, built.value);
+    assert_eq!(8080, built.port);
+
+    let builder: StructBuilderState<
+        (),
+        StructBuilderHostSet,
+        StructBuilderValueUnset,
+        StructBuilderPortUnset,
+    > = StructBuilder::builder().host("host".into());
+    assert!(builder.is_set("host"));
+    assert!(!builder.is_set("value"));
+}
+
+mod visible_states {
+    use type_safe_builder_macro::Builder;
+
+    #[derive(Builder)]
+    pub struct Struct {
+        pub host: String,
+        #[builder(default = 8080)]
+        pub port: u16,
+    }
+
+    pub fn configured() -> StructBuilderReady {
+        StructBuilder::builder().host("localhost".into())
+    }
+
+    pub fn build(builder: StructBuilderReady) -> Struct {
+        builder.build()
+    }
+
+    pub fn port_set(
+        builder: StructBuilderReady,
+    ) -> StructBuilderState<StructBuilderHostSet, StructBuilderPortSet> {
+        builder.port(80)
+    }
+
+    pub fn build_port_set(
+        builder: StructBuilderState<StructBuilderHostSet, StructBuilderPortSet>,
+    ) -> Struct {
+        builder.build()
+    }
+}
+
+#[test]
+fn builder_states_have_the_visibility_of_the_struct() {
+    let builder: visible_states::StructBuilderReady = visible_states::configured();
+    let built = visible_states::build(builder);
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+
+    let builder: visible_states::StructBuilderState<
+        visible_states::StructBuilderHostSet,
+        visible_states::StructBuilderPortSet,
+    > = visible_states::port_set(visible_states::configured());
+    let built = visible_states::build_port_set(builder);
+    assert_eq!(80, built.port);
+}
+
+mod visible_dynamic {
+    use type_safe_builder_macro::Builder;
+
+    #[derive(Builder)]
+    #[builder(partial, from_pairs, env_prefix = "VISIBLE_DYNAMIC_")]
+    pub struct Struct {
+        pub host: String,
+        #[builder(default = 8080)]
+        pub port: u16,
+    }
+
+    pub fn from_env() -> Result<Struct, StructBuilderEnvError> {
+        StructBuilder::from_env_with(|_| None)
+    }
+
+    pub fn first_field() -> &'static StructBuilderFieldInfo {
+        &StructBuilder::FIELDS[0]
+    }
+}
+
+#[test]
+fn dynamic_items_have_the_visibility_of_the_struct() {
+    let mut builder = visible_dynamic::StructDynBuilder::new();
+    builder.host("localhost".into()).unwrap();
+    let built = builder.try_build().unwrap();
+    assert_eq!("localhost", built.host);
+
+    let mut partial = visible_dynamic::StructPartial::new();
+    partial.port = Some(80);
+    match partial.try_build() {
+        Err(visible_dynamic::StructDynBuilderError::MissingFields(fields)) => {
+            assert_eq!(vec!["host"], fields)
+        }
+        _ => panic!("the host is missing"),
+    }
+
+    let error: visible_dynamic::StructBuilderEnvError = match visible_dynamic::from_env() {
+        Err(error) => error,
+        Ok(_) => panic!("the host is missing"),
+    };
+    assert_eq!(vec!["host"], error.missing_fields);
+
+    let info: &visible_dynamic::StructBuilderFieldInfo = visible_dynamic::first_field();
+    assert_eq!("host", info.name);
+}
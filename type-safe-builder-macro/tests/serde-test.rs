@@ -0,0 +1,116 @@
+#![cfg(feature = "serde")]
+
+use type_safe_builder_macro::Builder;
+
+#[test]
+fn deserialize_uses_builder_defaults_for_missing_fields() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(serde)]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+        #[builder(default)]
+        user: String,
+        #[builder(default = format!("{}:{}", host, port))]
+        address: String,
+    }
+
+    let built: Struct = serde_json::from_str(r#"{"host": "localhost", "user": "admin"}"#).unwrap();
+
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+    assert_eq!("admin", built.user);
+    assert_eq!("localhost:8080", built.address);
+}
+
+#[test]
+fn deserialize_reports_missing_required_field() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(serde)]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    let error = serde_json::from_str::<Struct>(r#"{"port": 80}"#).unwrap_err();
+
+    assert_eq!(
+        "missing field `host` at line 1 column 12",
+        error.to_string()
+    );
+}
+
+#[test]
+fn deserialize_honours_serde_attributes() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(serde)]
+    #[serde(rename_all = "camelCase", deny_unknown_fields)]
+    struct Struct<T> {
+        #[serde(rename = "name")]
+        host_name: String,
+        #[builder(default)]
+        retry_count: T,
+    }
+
+    let built: Struct<u8> =
+        serde_json::from_str(r#"{"name": "localhost", "retryCount": 3}"#).unwrap();
+
+    assert_eq!("localhost", built.host_name);
+    assert_eq!(3, built.retry_count);
+
+    let error = serde_json::from_str::<Struct<u8>>(r#"{"retryCount": 3}"#).unwrap_err();
+    assert!(error.to_string().starts_with("missing field `name`"));
+
+    assert!(serde_json::from_str::<Struct<u8>>(r#"{"name": "localhost", "other": 1}"#).is_err());
+}
+
+#[test]
+fn partial_can_be_serialized() {
+    #[allow(dead_code)]
+    #[derive(Builder, Debug)]
+    #[builder(partial, partial_attr(derive(serde::Serialize, serde::Deserialize)))]
+    struct Struct {
+        host: String,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    let partial: StructPartial = serde_json::from_str(r#"{"host": "localhost"}"#).unwrap();
+    assert_eq!(
+        r#"{"host":"localhost","port":null}"#,
+        serde_json::to_string(&partial).unwrap()
+    );
+
+    let built = partial.try_build().unwrap();
+    assert_eq!("localhost", built.host);
+    assert_eq!(8080, built.port);
+}
+
+mod no_prelude {
+    #![no_implicit_prelude]
+
+    use ::core::assert_eq;
+    use ::core::prelude::v1::test;
+    use ::type_safe_builder_macro::Builder;
+
+    #[derive(Builder)]
+    #[builder(serde)]
+    struct Struct {
+        host: ::std::string::String,
+        #[builder(default = 8080)]
+        port: u16,
+    }
+
+    #[test]
+    fn deserialize_without_prelude() {
+        let built: Struct = ::serde_json::from_str(r#"{"host": "localhost"}"#).unwrap();
+
+        assert_eq!("localhost", built.host);
+        assert_eq!(8080, built.port);
+    }
+}
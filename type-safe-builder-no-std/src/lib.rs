@@ -0,0 +1,161 @@
+//! Derives builders in a `#![no_std]` crate, so that building it checks the generated code
+//! doesn't need `std`. Only loading from the environment does, and is derived for the tests only.
+
+#![cfg_attr(not(test), no_std)]
+// The derived items are used by the tests only.
+#![cfg_attr(not(test), allow(dead_code))]
+// The derived public items must be documented.
+#![deny(missing_docs)]
+
+extern crate alloc;
+
+/// A public struct, whose derived public items must all be documented.
+pub mod documented {
+    use type_safe_builder_macro::Builder;
+
+    /// A documented struct.
+    #[derive(Builder)]
+    #[builder(to_builder, partial, from_pairs)]
+    pub struct Documented<T = u8> {
+        /// A required field.
+        pub name: ::alloc::string::String,
+        /// A defaulted field.
+        #[builder(default)]
+        pub value: T,
+    }
+}
+
+mod no_prelude {
+    #![no_implicit_prelude]
+
+    use ::type_safe_builder_macro::Builder;
+
+    #[allow(dead_code)]
+    struct Option;
+    #[allow(dead_code)]
+    struct Some;
+    #[allow(dead_code)]
+    struct None;
+    #[allow(dead_code)]
+    struct Default;
+    #[allow(dead_code)]
+    struct Result;
+    #[allow(dead_code)]
+    struct Vec;
+    #[allow(dead_code)]
+    struct String;
+
+    #[derive(::core::default::Default)]
+    struct Defaults {
+        from_struct_default: i32,
+    }
+
+    fn one() -> i32 {
+        1
+    }
+
+    #[derive(Builder)]
+    #[builder(derive(Debug))]
+    #[builder(build_into = ::alloc::boxed::Box<Self>)]
+    struct Struct<T> {
+        mandatory: i32,
+        #[builder(multi, default = 0)]
+        multi: i32,
+        #[builder(default = 4)]
+        defaulted: i32,
+        #[builder(default_with = one, requires = mandatory)]
+        with_fn: i32,
+        #[builder(default)]
+        generic: T,
+        #[builder(group = group, exactly_one, default)]
+        first: i32,
+        #[builder(group = group, default)]
+        second: i32,
+    }
+
+    #[derive(Builder)]
+    #[builder(default = struct)]
+    struct FromStructDefault {
+        from_struct_default: i32,
+    }
+
+    impl ::core::default::Default for FromStructDefault {
+        fn default() -> Self {
+            let defaults = Defaults {
+                from_struct_default: 5,
+            };
+            FromStructDefault {
+                from_struct_default: defaults.from_struct_default,
+            }
+        }
+    }
+
+    #[derive(Builder)]
+    #[builder(dynamic, from_pairs, partial)]
+    struct Dynamic {
+        #[cfg_attr(test, builder(env = "NAME"))]
+        name: ::alloc::string::String,
+        #[builder(default = 1)]
+        number: i32,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ::core::assert_eq;
+        use ::core::prelude::v1::test;
+
+        #[test]
+        fn can_derive_builder_without_prelude() {
+            let built = StructBuilder::builder()
+                .mandatory(1)
+                .multi(2)
+                .multi(3)
+                .second(6)
+                .build();
+
+            assert_eq!(1, built.mandatory);
+            assert_eq!(3, built.multi);
+            assert_eq!(4, built.defaulted);
+            assert_eq!(1, built.with_fn);
+            assert_eq!(0u8, built.generic);
+            assert_eq!(0, built.first);
+            assert_eq!(6, built.second);
+
+            let built = StructBuilder::builder().mandatory(2).build_over(*built);
+            assert_eq!(2, built.mandatory);
+            assert_eq!(3, built.multi);
+
+            let built = FromStructDefaultBuilder::builder().build();
+            assert_eq!(5, built.from_struct_default);
+        }
+
+        #[test]
+        fn can_use_dynamic_builder_without_prelude() {
+            let mut builder = <DynamicDynBuilder as ::core::convert::From<_>>::from(
+                DynamicBuilder::builder().number(2),
+            );
+            builder.set_by_name("name", "value").unwrap();
+            let built = builder.try_build().unwrap();
+            assert_eq!("value", built.name);
+            assert_eq!(2, built.number);
+
+            let built = DynamicBuilder::from_pairs([("name", "value")]).unwrap();
+            assert_eq!("value", built.name);
+
+            let built = DynamicBuilder::from_env_with(|name| {
+                (name == "NAME").then(|| ::alloc::string::ToString::to_string("value"))
+            })
+            .unwrap();
+            assert_eq!("value", built.name);
+
+            let partial = DynamicPartial {
+                name: ::core::option::Option::Some(::alloc::string::ToString::to_string("value")),
+                number: ::core::option::Option::None,
+            };
+            let built = DynamicPartial::new().merge(partial).try_build().unwrap();
+            assert_eq!("value", built.name);
+            assert_eq!(1, built.number);
+        }
+    }
+}